@@ -0,0 +1,85 @@
+//! Throughput benchmark for the literal prefilter on clean traffic, plus
+//! worst-case timings for each detector on large, separator-heavy input.
+//!
+//! Run with `cargo bench --bench detection`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use zentinel_agent_ai_gateway::detection::{
+    InlineDataDetector, JailbreakDetector, PiiDetector, PromptInjectionDetector,
+};
+
+const CLEAN_MESSAGES: &[&str] = &[
+    "What is the capital of France?",
+    "Can you help me write a function that sorts a list of integers in Rust?",
+    "Summarize the following meeting notes into three bullet points for the team.",
+    "Translate 'good morning' into Spanish, German and Japanese.",
+    "Explain the difference between TCP and UDP in simple terms.",
+    "Write a short poem about the ocean at sunrise.",
+];
+
+const ITERATIONS: usize = 20_000;
+
+fn measure(name: &str, mut detect: impl FnMut(&str) -> bool) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        for message in CLEAN_MESSAGES {
+            black_box(detect(black_box(message)));
+        }
+    }
+    let elapsed = start.elapsed();
+    let per_sec = (ITERATIONS * CLEAN_MESSAGES.len()) as f64 / elapsed.as_secs_f64();
+    println!("{:<40} {:>10.2?} {:>14.0} msg/s", name, elapsed, per_sec);
+    elapsed
+}
+
+/// Inputs that keep the phone, card, IP and base64 scanners busy
+fn pathological_inputs() -> Vec<(&'static str, String)> {
+    vec![
+        ("digit separators", "1-2 3.4 ".repeat(32_768)),
+        ("dotted quads", "10.0.0.".repeat(32_768)),
+        ("base64 run", "QUJD".repeat(65_536)),
+        ("trigger words", "ignore mode filter ".repeat(16_384)),
+    ]
+}
+
+fn measure_worst_case(name: &str, detect: impl Fn(&str) -> bool) {
+    for (input_name, input) in pathological_inputs() {
+        let start = Instant::now();
+        black_box(detect(black_box(&input)));
+        println!(
+            "{:<40} {:>10.2?}",
+            format!("{} / {}", name, input_name),
+            start.elapsed()
+        );
+    }
+}
+
+fn main() {
+    let injection = PromptInjectionDetector::new();
+    let injection_full = PromptInjectionDetector::without_prefilter();
+    let jailbreak = JailbreakDetector::new();
+    let jailbreak_full = JailbreakDetector::without_prefilter();
+
+    let full = measure("prompt injection (regex only)", |m| {
+        injection_full.detect(m).is_some()
+    });
+    let fast = measure("prompt injection (prefilter)", |m| {
+        injection.detect(m).is_some()
+    });
+    println!("  speedup: {:.1}x", full.as_secs_f64() / fast.as_secs_f64());
+
+    let full = measure("jailbreak (regex only)", |m| {
+        jailbreak_full.detect(m).is_some()
+    });
+    let fast = measure("jailbreak (prefilter)", |m| jailbreak.detect(m).is_some());
+    println!("  speedup: {:.1}x", full.as_secs_f64() / fast.as_secs_f64());
+
+    println!("\nworst case (256 KiB input):");
+    let pii = PiiDetector::new();
+    let inline_data = InlineDataDetector::new();
+    measure_worst_case("prompt injection", |m| injection.detect(m).is_some());
+    measure_worst_case("jailbreak", |m| jailbreak.detect(m).is_some());
+    measure_worst_case("pii", |m| !pii.detect(m).is_empty());
+    measure_worst_case("inline data", |m| !inline_data.detect(m, 4096).is_empty());
+}
@@ -0,0 +1,101 @@
+//! Concurrent throughput of the per-request state store: a single map
+//! behind one lock against the sharded store the agent uses.
+//!
+//! Run with `cargo bench --bench request_state`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zentinel_agent_ai_gateway::state::{RequestStore, ShardedStore};
+
+/// Every request in flight shares this one lock
+#[derive(Default)]
+struct SingleLockStore<V>(Mutex<HashMap<String, V>>);
+
+impl<V: Send> RequestStore<V> for SingleLockStore<V> {
+    fn insert_new(&self, key: String, value: V) -> Result<(), V> {
+        let mut map = self.0.lock().unwrap();
+        if map.contains_key(&key) {
+            return Err(value);
+        }
+        map.insert(key, value);
+        Ok(())
+    }
+
+    fn update(&self, key: &str, f: &mut dyn FnMut(&mut V)) -> bool {
+        self.0.lock().unwrap().get_mut(key).map(f).is_some()
+    }
+
+    fn remove(&self, key: &str) -> Option<V> {
+        self.0.lock().unwrap().remove(key)
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.0.lock().unwrap().contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    fn clear(&self) {
+        self.0.lock().unwrap().clear()
+    }
+}
+
+const THREADS: usize = 8;
+const REQUESTS_PER_THREAD: usize = 2_000;
+const CHUNKS_PER_REQUEST: usize = 8;
+const CHUNK: &[u8] = &[b'x'; 4096];
+
+/// Drive requests through the store from several threads at once
+///
+/// Each request is inserted, receives its body chunks (copied in under the
+/// lock, as the agent decodes them), and is removed.
+fn measure(name: &str, store: Arc<dyn RequestStore<Vec<Vec<u8>>>>) -> Duration {
+    let start = Instant::now();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let store = Arc::clone(&store);
+            std::thread::spawn(move || {
+                for i in 0..REQUESTS_PER_THREAD {
+                    let key = format!("{}-{}", t, i);
+                    store.insert_new(key.clone(), Vec::new()).unwrap();
+                    for _ in 0..CHUNKS_PER_REQUEST {
+                        store.update(&key, &mut |chunks| chunks.push(CHUNK.to_vec()));
+                    }
+                    black_box(store.remove(&key));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    let per_sec = (THREADS * REQUESTS_PER_THREAD) as f64 / elapsed.as_secs_f64();
+    println!("{:<40} {:>10.2?} {:>14.0} req/s", name, elapsed, per_sec);
+    elapsed
+}
+
+fn main() {
+    // Sharding only pays off when threads actually run in parallel
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    println!(
+        "{} threads on {} CPUs, {} body chunks per request:",
+        THREADS, cpus, CHUNKS_PER_REQUEST
+    );
+    let single = measure(
+        "single lock",
+        Arc::new(SingleLockStore::<Vec<Vec<u8>>>::default()),
+    );
+    let sharded = measure(
+        "sharded store",
+        Arc::new(ShardedStore::<Vec<Vec<u8>>>::default()),
+    );
+    println!(
+        "  speedup: {:.1}x",
+        single.as_secs_f64() / sharded.as_secs_f64()
+    );
+}
@@ -0,0 +1,249 @@
+//! Structured analysis results.
+//!
+//! [`AnalysisResult`] is what the agent computes for a request before it is
+//! turned into a protocol response, so the same checks can be used directly
+//! from library code without running the agent transport.
+
+use crate::detection::PiiType;
+use crate::providers::schema::SchemaValidationResult;
+use crate::providers::AiProvider;
+use crate::ratelimit::RateLimitResult;
+use serde::{Deserialize, Serialize};
+
+/// Final decision for an analyzed request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnalysisDecision {
+    /// Allow the request
+    #[default]
+    Allow,
+    /// Blocked by a policy or detection
+    Block,
+    /// Rejected because the body failed schema validation
+    Invalid,
+    /// Rejected by rate limiting
+    RateLimited,
+    /// Could not be inspected (e.g. a scan timeout) and failing closed
+    Error,
+}
+
+impl AnalysisDecision {
+    /// HTTP status the proxy should return, or `None` when allowed
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            AnalysisDecision::Allow => None,
+            AnalysisDecision::Block => Some(403),
+            AnalysisDecision::Invalid => Some(400),
+            AnalysisDecision::RateLimited => Some(429),
+            AnalysisDecision::Error => Some(500),
+        }
+    }
+
+    /// Decision name for logs and stored results, e.g. `rate-limited`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnalysisDecision::Allow => "allow",
+            AnalysisDecision::Block => "block",
+            AnalysisDecision::Invalid => "invalid",
+            AnalysisDecision::RateLimited => "rate-limited",
+            AnalysisDecision::Error => "error",
+        }
+    }
+}
+
+/// Severity attached to a reason code for SIEM consumers
+///
+/// The numeric value (see [`DetectionSeverity::level`]) is stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionSeverity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl DetectionSeverity {
+    /// Numeric severity level (0 = info .. 4 = critical)
+    pub fn level(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Get the severity name as a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetectionSeverity::Info => "info",
+            DetectionSeverity::Low => "low",
+            DetectionSeverity::Medium => "medium",
+            DetectionSeverity::High => "high",
+            DetectionSeverity::Critical => "critical",
+        }
+    }
+
+    /// Built-in severity for a reason code
+    pub fn default_for(reason_code: &str) -> Self {
+        match reason_code {
+            "PROMPT_INJECTION"
+            | "JAILBREAK_ATTEMPT"
+            | "PRIVATE_KEY_DETECTED"
+            | "ASSISTANT_PREFILL_INJECTION"
+            | "HIDDEN_UNICODE" => DetectionSeverity::High,
+            "PII_DETECTED"
+            | "MODEL_NOT_ALLOWED"
+            | "MODEL_REQUIRED"
+            | "MESSAGE_TOO_LONG"
+            | "SAMPLING_ABUSE"
+            | "REPETITION_STUFFING"
+            | "DATA_URI_BLOCKED"
+            | "RESPONSE_TOKEN_LIMIT"
+            | "CUSTOM_DETECTION"
+            | "KEYWORD_BLOCKED"
+            | "MISSING_REQUIRED_HEADER"
+            | "SAMPLING_POLICY_VIOLATION"
+            | "TLS_POLICY_VIOLATION"
+            | "REALTIME_BLOCKED"
+            | "EXTERNAL_MODERATION_FLAGGED" => DetectionSeverity::Medium,
+            "SCHEMA_VALIDATION_FAILED"
+            | "TOKEN_LIMIT_EXCEEDED"
+            | "PROMPT_TOKEN_LIMIT"
+            | "PROMPT_INJECTION_IN_CODE"
+            | "RATE_LIMIT_EXCEEDED"
+            | "GLOBAL_RATE_LIMIT_EXCEEDED"
+            | "INVALID_UTF8"
+            | "INVALID_JSON"
+            | "BODY_DECODE_ERROR"
+            | "CONFIG_REJECTED"
+            | "SCAN_TIMEOUT"
+            | "PROCESSING_TIMEOUT"
+            | "CONTEXT_NEAR_LIMIT"
+            | "CONTEXT_WINDOW_EXCEEDED"
+            | "EXTERNAL_MODERATION_ERROR" => DetectionSeverity::Low,
+            _ => DetectionSeverity::Info,
+        }
+    }
+}
+
+/// Result of running all checks on a request body
+#[derive(Debug, Clone)]
+pub struct AnalysisResult {
+    /// Unique ID of this decision, returned as `X-AI-Gateway-Decision-Id`
+    /// and recorded in the audit metadata and the detection database
+    pub decision_id: String,
+    /// Final decision
+    pub decision: AnalysisDecision,
+    /// Primary reason for a block decision (the first check that blocked)
+    pub block_reason: Option<String>,
+    /// Every reason that would have blocked, in check order
+    pub block_reasons: Vec<String>,
+    /// Audit tags
+    pub tags: Vec<String>,
+    /// Reason codes for everything that was detected or enforced
+    pub reason_codes: Vec<String>,
+    /// Highest severity among the reason codes
+    pub severity: Option<DetectionSeverity>,
+    /// Detector findings (e.g. `prompt-injection`, `jailbreak-attempt`)
+    pub detections: Vec<String>,
+    /// PII types found in the scanned content
+    pub pii_types: Vec<PiiType>,
+    /// Distinct matched values per PII type, in `pii_types` order
+    pub pii_counts: Vec<(PiiType, usize)>,
+    /// Detected provider
+    pub provider: AiProvider,
+    /// Model from the request, after `model_aliases`
+    pub model: Option<String>,
+    /// Model as the client sent it, when an alias renamed it
+    pub original_model: Option<String>,
+    /// Whether the body was recognized as an AI request
+    pub recognized: bool,
+    /// Estimated prompt tokens
+    pub estimated_tokens: u32,
+    /// Estimated output tokens across all requested completions
+    pub estimated_output_tokens: u32,
+    /// Estimated cost in USD (prompt plus output tokens)
+    pub estimated_cost: f64,
+    /// Whether the model has a known price; otherwise `estimated_cost` uses
+    /// the fallback rate
+    pub cost_known: bool,
+    /// Schema validation outcome (when enabled)
+    pub schema_validation: Option<SchemaValidationResult>,
+    /// Rate limit outcome (when enabled)
+    pub rate_limit: Option<RateLimitResult>,
+    /// Estimated prompt share of the model's context window, in percent, when
+    /// over `context_warn_fraction`
+    pub context_usage_percent: Option<u32>,
+    /// Token limit from `per_model_token_limits`, when the model has one
+    pub model_token_limit: Option<u32>,
+}
+
+/// A fresh random (v4 UUID) decision ID
+pub(crate) fn new_decision_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl AnalysisResult {
+    /// Create an empty (allowing) result for the given provider
+    pub fn new(provider: AiProvider) -> Self {
+        Self::with_tag_prefix(provider, "ai-gateway")
+    }
+
+    /// Create an empty result whose first audit tag is `tag_prefix`
+    pub fn with_tag_prefix(provider: AiProvider, tag_prefix: &str) -> Self {
+        Self {
+            decision_id: new_decision_id(),
+            decision: AnalysisDecision::Allow,
+            block_reason: None,
+            block_reasons: Vec::new(),
+            tags: vec![tag_prefix.to_string()],
+            reason_codes: Vec::new(),
+            severity: None,
+            detections: Vec::new(),
+            pii_types: Vec::new(),
+            pii_counts: Vec::new(),
+            provider,
+            model: None,
+            original_model: None,
+            recognized: false,
+            estimated_tokens: 0,
+            estimated_output_tokens: 0,
+            estimated_cost: 0.0,
+            cost_known: false,
+            schema_validation: None,
+            rate_limit: None,
+            context_usage_percent: None,
+            model_token_limit: None,
+        }
+    }
+
+    /// Record a reason code and raise the overall severity to match
+    pub fn add_reason(&mut self, reason_code: &str, severity: DetectionSeverity) {
+        self.reason_codes.push(reason_code.to_string());
+        self.severity = self.severity.max(Some(severity));
+    }
+
+    /// Set a single block reason
+    pub fn set_block_reason(&mut self, reason: String) {
+        self.block_reasons = vec![reason.clone()];
+        self.block_reason = Some(reason);
+    }
+
+    /// Whether the request should be rejected
+    pub fn is_blocked(&self) -> bool {
+        self.decision != AnalysisDecision::Allow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_reason_keeps_highest_severity() {
+        let mut result = AnalysisResult::new(AiProvider::OpenAI);
+        result.add_reason("PII_DETECTED", DetectionSeverity::Medium);
+        result.add_reason("PROMPT_INJECTION", DetectionSeverity::High);
+        result.add_reason("RATE_LIMIT_EXCEEDED", DetectionSeverity::Low);
+        assert_eq!(result.severity, Some(DetectionSeverity::High));
+        assert_eq!(result.reason_codes.len(), 3);
+        assert_eq!(DetectionSeverity::High.level(), 3);
+    }
+}
@@ -0,0 +1,189 @@
+//! Scan result cache.
+//!
+//! Retried and polled requests often carry identical bodies. A bounded LRU
+//! keyed by a hash of the scanned content lets repeats within a short TTL
+//! reuse the detector findings instead of scanning again. Only stateless
+//! findings belong here: rate limits and cost are always evaluated afresh.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+/// Default lifetime of a cached entry
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// One cached value
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    /// Recency stamp of the entry's latest use in `ScanCache::order`
+    used: u64,
+}
+
+/// Bounded least-recently-used cache with a time-to-live
+///
+/// Keys are hashed with a per-process random seed, so clients cannot craft
+/// two payloads that collide and share a cached result.
+///
+/// Each use appends the key and a fresh stamp to `order`; eviction pops from
+/// the front, skipping stamps an entry has since moved past. Stale stamps
+/// are swept once they outnumber the entries, so every operation is
+/// amortized O(1).
+#[derive(Debug)]
+pub struct ScanCache<V> {
+    capacity: usize,
+    ttl: Duration,
+    hasher: RandomState,
+    entries: HashMap<u64, Entry<V>>,
+    /// Key and stamp of each use, least recent first
+    order: VecDeque<(u64, u64)>,
+    clock: u64,
+}
+
+impl<V: Clone> ScanCache<V> {
+    /// Create a cache holding up to `capacity` entries (0 = disabled)
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            hasher: RandomState::new(),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            clock: 0,
+        }
+    }
+
+    /// Whether the cache stores anything
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0 && !self.ttl.is_zero()
+    }
+
+    /// Number of live and expired entries currently held
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Hash a key with this cache's seed
+    pub fn key<K: Hash>(&self, key: &K) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    /// Look up a value, refreshing its recency; expired entries are dropped
+    pub fn get(&mut self, key: u64) -> Option<V> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(&key)?;
+        if entry.inserted.elapsed() >= self.ttl {
+            self.entries.remove(&key);
+            return None;
+        }
+        entry.used = self.clock;
+        let value = entry.value.clone();
+        self.touch(key);
+        Some(value)
+    }
+
+    /// Store a value, evicting the least recently used entry when full
+    pub fn insert(&mut self, key: u64, value: V) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.clock += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.evict_least_recent();
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted: Instant::now(),
+                used: self.clock,
+            },
+        );
+        self.touch(key);
+    }
+
+    /// Record a use of `key` at the current clock
+    fn touch(&mut self, key: u64) {
+        self.order.push_back((key, self.clock));
+        if self.order.len() > 2 * self.capacity {
+            let entries = &self.entries;
+            self.order
+                .retain(|(key, stamp)| entries.get(key).is_some_and(|e| e.used == *stamp));
+        }
+    }
+
+    /// Drop the entry whose latest use is oldest
+    fn evict_least_recent(&mut self) {
+        while let Some((key, stamp)) = self.order.pop_front() {
+            if self.entries.get(&key).is_some_and(|e| e.used == stamp) {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_after_insert() {
+        let mut cache = ScanCache::new(4, DEFAULT_TTL);
+        let key = cache.key(&("openai", "body"));
+        assert_eq!(cache.get(key), None);
+        cache.insert(key, 7);
+        assert_eq!(cache.get(key), Some(7));
+        assert_ne!(key, cache.key(&("anthropic", "body")));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = ScanCache::new(2, DEFAULT_TTL);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        assert_eq!(cache.get(1), Some("a"));
+        cache.insert(3, "c");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(1), Some("a"));
+        assert_eq!(cache.get(3), Some("c"));
+    }
+
+    #[test]
+    fn test_recency_queue_stays_bounded() {
+        let mut cache = ScanCache::new(2, DEFAULT_TTL);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        for _ in 0..1000 {
+            assert_eq!(cache.get(1), Some("a"));
+        }
+        assert!(cache.order.len() <= 4);
+        cache.insert(3, "c");
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(1), Some("a"));
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let mut cache = ScanCache::new(2, Duration::from_millis(20));
+        cache.insert(1, "a");
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.get(1), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_cache_stores_nothing() {
+        let mut cache = ScanCache::new(0, DEFAULT_TTL);
+        cache.insert(1, "a");
+        assert_eq!(cache.get(1), None);
+    }
+}
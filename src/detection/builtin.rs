@@ -0,0 +1,131 @@
+//! Compilation of the built-in detection patterns.
+//!
+//! The built-in patterns are constants, so one that does not compile is a
+//! bug, but it must not take the agent down with it. Pattern sets are
+//! compiled whole and, if that fails, one pattern at a time: patterns that do
+//! not compile are left out and reported, and the rest keep detecting.
+
+use crate::GatewayError;
+use regex::{Match, Regex, RegexSet};
+use std::borrow::Cow;
+use tracing::warn;
+
+/// A built-in regex; one that did not compile never matches
+pub(crate) struct BuiltinRegex(Option<Regex>);
+
+impl BuiltinRegex {
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        self.0.as_ref().is_some_and(|regex| regex.is_match(text))
+    }
+
+    pub(crate) fn find_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Match<'a>> + 'a {
+        self.0.iter().flat_map(move |regex| regex.find_iter(text))
+    }
+
+    pub(crate) fn replace_all<'t>(&self, text: &'t str, replacement: &str) -> Cow<'t, str> {
+        match self.0 {
+            Some(ref regex) => regex.replace_all(text, replacement),
+            None => Cow::Borrowed(text),
+        }
+    }
+}
+
+fn invalid(detector: &'static str, pattern: &str, error: &regex::Error) -> GatewayError {
+    GatewayError::InvalidPattern {
+        detector,
+        pattern: pattern.to_string(),
+        reason: error.to_string(),
+    }
+}
+
+/// Compile one built-in regex, recording an error if it does not compile
+pub(crate) fn regex(
+    detector: &'static str,
+    pattern: &str,
+    errors: &mut Vec<GatewayError>,
+) -> BuiltinRegex {
+    match Regex::new(pattern) {
+        Ok(regex) => BuiltinRegex(Some(regex)),
+        Err(e) => {
+            errors.push(invalid(detector, pattern, &e));
+            BuiltinRegex(None)
+        }
+    }
+}
+
+/// Compile a built-in pattern set, leaving out patterns that do not compile
+///
+/// Returns the set and, for each pattern in it, its index in `patterns`.
+pub(crate) fn regex_set(
+    detector: &'static str,
+    patterns: &[&str],
+    errors: &mut Vec<GatewayError>,
+) -> (RegexSet, Vec<usize>) {
+    if let Ok(set) = RegexSet::new(patterns) {
+        return (set, (0..patterns.len()).collect());
+    }
+
+    let kept: Vec<usize> = patterns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, pattern)| match Regex::new(pattern) {
+            Ok(_) => Some(i),
+            Err(e) => {
+                errors.push(invalid(detector, pattern, &e));
+                None
+            }
+        })
+        .collect();
+    match RegexSet::new(kept.iter().map(|&i| patterns[i])) {
+        Ok(set) => (set, kept),
+        // Every pattern compiles alone but not together, e.g. a size limit
+        Err(e) => {
+            errors.push(invalid(detector, &patterns.join(" | "), &e));
+            (RegexSet::empty(), Vec::new())
+        }
+    }
+}
+
+/// Keep a detector built with errors, warning about what it cannot detect
+pub(crate) fn degraded<T>((detector, errors): (T, Vec<GatewayError>)) -> T {
+    for e in &errors {
+        warn!("{}, detecting without it", e);
+    }
+    detector
+}
+
+/// Reject a detector built with errors, returning the first
+pub(crate) fn strict<T>((detector, errors): (T, Vec<GatewayError>)) -> Result<T, GatewayError> {
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(detector),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bad_pattern_is_left_out_of_set() {
+        let mut errors = Vec::new();
+        let (set, kept) = regex_set("test", &["ignore", "(unclosed", "bypass"], &mut errors);
+        assert_eq!(kept, vec![0, 2]);
+        assert!(set.is_match("please bypass"));
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            GatewayError::InvalidPattern { detector: "test", ref pattern, .. } if pattern == "(unclosed"
+        ));
+    }
+
+    #[test]
+    fn test_bad_regex_never_matches() {
+        let mut errors = Vec::new();
+        let regex = regex("test", "(unclosed", &mut errors);
+        assert_eq!(errors.len(), 1);
+        assert!(!regex.is_match("(unclosed"));
+        assert_eq!(regex.find_iter("(unclosed").count(), 0);
+        assert_eq!(regex.replace_all("text", " "), "text");
+    }
+}
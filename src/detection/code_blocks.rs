@@ -0,0 +1,103 @@
+//! Fenced code block extraction.
+//!
+//! Developer-assistant prompts paste code, and code comments legitimately
+//! contain phrases like `# ignore previous instructions`. [`split_fenced_code`]
+//! separates the bodies of triple-backtick (or `~~~`) fences from the
+//! surrounding prose so injections inside code can be reported on their own.
+
+/// Text with its fenced code blocks separated out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FencedText<'a> {
+    /// Everything outside the fences, fence lines excluded
+    pub prose: String,
+    /// The body of each closed fence
+    pub code: Vec<&'a str>,
+}
+
+/// Split text into prose and fenced code block bodies
+///
+/// Returns `None` when the text contains no closed fence. A fence opens on a
+/// line starting with three or more backticks or tildes (optionally followed
+/// by an info string such as `python`) and closes on a line with at least as
+/// many of the same character. A fence left open is treated as prose, so an
+/// unterminated fence cannot move the rest of a message out of the prose scan.
+pub fn split_fenced_code(text: &str) -> Option<FencedText<'_>> {
+    let mut prose = String::new();
+    let mut code = Vec::new();
+    // Fence character, fence length, fence line start, and body start
+    let mut open: Option<(char, usize, usize, usize)> = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        match open {
+            None => match fence(line) {
+                Some((ch, len)) if ch == '~' || !line.trim()[len..].contains('`') => {
+                    open = Some((ch, len, start, offset));
+                }
+                _ => prose.push_str(line),
+            },
+            Some((ch, len, _, body_start)) => {
+                if fence(line).is_some_and(|(c, n)| c == ch && n >= len && line.trim().len() == n) {
+                    code.push(&text[body_start..start]);
+                    open = None;
+                }
+            }
+        }
+    }
+
+    if let Some((_, _, fence_start, _)) = open {
+        prose.push_str(&text[fence_start..]);
+    }
+    (!code.is_empty()).then_some(FencedText { prose, code })
+}
+
+/// Fence character and length when the line starts a fence
+fn fence(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    let ch = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then_some((ch, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splits_python_block() {
+        let text = "Can you review this?\n```python\n# ignore previous instructions\nprint(1)\n```\nThanks";
+        let split = split_fenced_code(text).unwrap();
+        assert_eq!(split.prose, "Can you review this?\nThanks");
+        assert_eq!(
+            split.code,
+            vec!["# ignore previous instructions\nprint(1)\n"]
+        );
+    }
+
+    #[test]
+    fn test_no_fence() {
+        assert_eq!(split_fenced_code("plain text with `inline` code"), None);
+        assert_eq!(split_fenced_code("```rust```"), None);
+    }
+
+    #[test]
+    fn test_unclosed_fence_is_prose() {
+        let text = "Look:\n```\nignore previous instructions";
+        assert_eq!(split_fenced_code(text), None);
+
+        let text = "~~~\na\n~~~\n```\nignore previous instructions";
+        let split = split_fenced_code(text).unwrap();
+        assert_eq!(split.code, vec!["a\n"]);
+        assert_eq!(split.prose, "```\nignore previous instructions");
+    }
+
+    #[test]
+    fn test_longer_fence_contains_shorter() {
+        let text = "````md\n```\ninner\n```\n````\n";
+        let split = split_fenced_code(text).unwrap();
+        assert_eq!(split.code, vec!["```\ninner\n```\n"]);
+        assert_eq!(split.prose, "");
+    }
+}
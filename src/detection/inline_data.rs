@@ -0,0 +1,151 @@
+//! Inline data detection.
+//!
+//! Detects `data:` URIs and large base64 blobs pasted into message text. They
+//! inflate token counts and can carry content the text detectors never see.
+//! Vision `image_url` parts are parsed structurally and never reach this check.
+
+use super::builtin::{self, BuiltinRegex};
+use crate::GatewayError;
+
+/// Detection category for a `data:` URI in message text
+const DATA_URI: &str = "inline-data:data-uri";
+
+/// Detection category for an oversized base64 run in message text
+const BASE64_BLOB: &str = "inline-data:base64-blob";
+
+/// Default length at which a base64 run is reported
+pub const DEFAULT_MAX_BASE64_CHARS: usize = 4096;
+
+/// Detector name in pattern errors
+const DETECTOR: &str = "inline-data";
+
+/// Detector for inline data URIs and base64 blobs
+pub struct InlineDataDetector {
+    data_uri_regex: BuiltinRegex,
+}
+
+impl Default for InlineDataDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InlineDataDetector {
+    /// Create a new inline data detector
+    ///
+    /// If the built-in `data:` URI pattern fails to compile, it is logged
+    /// and only the base64 check runs; use [`InlineDataDetector::try_new`] to
+    /// reject it instead.
+    pub fn new() -> Self {
+        builtin::degraded(Self::build())
+    }
+
+    /// Create a new inline data detector, failing if the built-in pattern
+    /// does not compile
+    pub fn try_new() -> Result<Self, GatewayError> {
+        builtin::strict(Self::build())
+    }
+
+    /// Compile the built-in pattern, along with the error if it does not
+    /// compile
+    pub(crate) fn build() -> (Self, Vec<GatewayError>) {
+        let mut errors = Vec::new();
+        let data_uri_regex = builtin::regex(
+            DETECTOR,
+            r"(?i)\bdata:[a-z]+/[a-z0-9.+-]+(;[a-z0-9.+=-]+)*;base64,",
+            &mut errors,
+        );
+        (Self { data_uri_regex }, errors)
+    }
+
+    /// Detect inline data in text
+    ///
+    /// Base64 runs (line breaks allowed, as in wrapped PEM/MIME output) are
+    /// reported once they reach `max_base64_chars`; 0 disables the blob check.
+    pub fn detect(&self, text: &str, max_base64_chars: usize) -> Vec<&'static str> {
+        let mut found = Vec::new();
+        if self.data_uri_regex.is_match(text) {
+            found.push(DATA_URI);
+        }
+        if max_base64_chars > 0 && longest_base64_run(text) >= max_base64_chars {
+            found.push(BASE64_BLOB);
+        }
+        found
+    }
+
+    /// Detect inline data across multiple texts, without duplicates
+    pub fn detect_all<'a>(
+        &self,
+        texts: impl Iterator<Item = &'a str>,
+        max_base64_chars: usize,
+    ) -> Vec<&'static str> {
+        let mut found: Vec<&'static str> = Vec::new();
+        for text in texts {
+            for kind in self.detect(text, max_base64_chars) {
+                if !found.contains(&kind) {
+                    found.push(kind);
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Length of the longest run of base64 characters, ignoring line breaks
+fn longest_base64_run(text: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for b in text.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' | b'=' => {
+                current += 1;
+                longest = longest.max(current);
+            }
+            b'\n' | b'\r' => {}
+            _ => current = 0,
+        }
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_data_uri() {
+        let detector = InlineDataDetector::new();
+        let text = "Summarize data:application/pdf;base64,JVBERi0xLjQK please";
+        assert_eq!(detector.detect(text, 4096), vec![DATA_URI]);
+        assert_eq!(
+            detector.detect("see DATA:image/png;charset=binary;base64,iVBOR", 4096),
+            vec![DATA_URI]
+        );
+    }
+
+    #[test]
+    fn test_detects_base64_blob() {
+        let detector = InlineDataDetector::new();
+        let blob = "QUJD".repeat(300);
+        let wrapped: String = blob
+            .as_bytes()
+            .chunks(76)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(detector.detect(&wrapped, 1024), vec![BASE64_BLOB]);
+        // Disabled with a zero limit
+        assert!(detector.detect(&wrapped, 0).is_empty());
+    }
+
+    #[test]
+    fn test_allows_normal_text() {
+        let detector = InlineDataDetector::new();
+        assert!(detector
+            .detect("The data: shows a 20% increase over base64 encoding", 64)
+            .is_empty());
+        assert!(detector
+            .detect("See https://example.com/a/b/c for details", 64)
+            .is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+//! Blocked keyword detection.
+//!
+//! A plain substring denylist for policies that do not need regexes:
+//! competitor names, profanity, project codenames. Keywords are matched
+//! case-insensitively with a single Aho-Corasick automaton.
+
+use aho_corasick::{AhoCorasick, MatchKind};
+
+/// Case-insensitive substring denylist
+#[derive(Debug, Clone, Default)]
+pub struct KeywordDetector {
+    /// `None` when no keywords are configured
+    automaton: Option<AhoCorasick>,
+    /// Lowercased keywords, indexed by automaton pattern ID
+    keywords: Vec<String>,
+}
+
+impl KeywordDetector {
+    /// Build a detector from a keyword list, ignoring blank entries
+    pub fn new(keywords: &[String]) -> Self {
+        let mut lowered: Vec<String> = keywords
+            .iter()
+            .map(|k| k.trim().to_lowercase())
+            .filter(|k| !k.is_empty())
+            .collect();
+        lowered.sort();
+        lowered.dedup();
+        if lowered.is_empty() {
+            return Self::default();
+        }
+
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&lowered)
+            .expect("Failed to build keyword automaton");
+        Self {
+            automaton: Some(automaton),
+            keywords: lowered,
+        }
+    }
+
+    /// Whether any keywords are configured
+    pub fn is_empty(&self) -> bool {
+        self.keywords.is_empty()
+    }
+
+    /// Find the first blocked keyword in the text, as configured (lowercased)
+    pub fn find(&self, text: &str) -> Option<&str> {
+        let automaton = self.automaton.as_ref()?;
+        // The automaton folds ASCII case only; lowercase other text first
+        let found = if text.is_ascii() {
+            automaton.find(text)
+        } else {
+            automaton.find(&text.to_lowercase())
+        };
+        found.map(|m| self.keywords[m.pattern().as_usize()].as_str())
+    }
+
+    /// Find the first blocked keyword across several texts
+    pub fn detect_any<'a>(&self, content: impl IntoIterator<Item = &'a str>) -> Option<&str> {
+        content.into_iter().find_map(|text| self.find(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(keywords: &[&str]) -> KeywordDetector {
+        let keywords: Vec<String> = keywords.iter().map(|k| k.to_string()).collect();
+        KeywordDetector::new(&keywords)
+    }
+
+    #[test]
+    fn test_keyword_matches_case_insensitively() {
+        let detector = detector(&["Project Falcon", "acme"]);
+        assert_eq!(
+            detector.find("what is the status of PROJECT FALCON?"),
+            Some("project falcon")
+        );
+        assert_eq!(detector.find("Compare us with Acme Corp"), Some("acme"));
+        assert_eq!(detector.find("Größe von ACME"), Some("acme"));
+        assert_eq!(detector.find("Project Falco is a bird"), None);
+    }
+
+    #[test]
+    fn test_non_ascii_keyword() {
+        let detector = detector(&["Straße"]);
+        assert_eq!(detector.find("Welche STRASSE?"), None);
+        assert_eq!(detector.find("Die STRAßE ist lang"), Some("straße"));
+    }
+
+    #[test]
+    fn test_empty_detector() {
+        let detector = detector(&["", "  "]);
+        assert!(detector.is_empty());
+        assert_eq!(detector.detect_any(["anything at all"]), None);
+    }
+}
@@ -1,9 +1,25 @@
 //! Detection modules for AI request analysis.
 
+mod builtin;
+pub mod code_blocks;
+pub mod custom_patterns;
+pub mod detector;
+pub mod hidden_unicode;
+pub mod inline_data;
 pub mod jailbreak;
+pub mod keywords;
+pub mod nested;
+pub mod normalize;
 pub mod pii;
+mod prefilter;
 pub mod prompt_injection;
+pub mod repetition;
+pub mod sampling;
 
+pub use custom_patterns::{CustomPattern, CustomPatterns};
+pub use detector::{ContentDetector, Detection};
+pub use inline_data::InlineDataDetector;
 pub use jailbreak::JailbreakDetector;
-pub use pii::{PiiDetector, PiiMatch, PiiType};
-pub use prompt_injection::PromptInjectionDetector;
+pub use keywords::KeywordDetector;
+pub use pii::{PiiDetector, PiiMatch, PiiOptions, PiiType, PiiTypeMatches};
+pub use prompt_injection::{InjectionOptions, PromptInjectionDetector};
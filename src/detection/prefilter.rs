@@ -0,0 +1,64 @@
+//! Literal prefilter for regex-based detectors.
+//!
+//! Most detection patterns contain a required literal ("ignore", "bypass",
+//! "mode", ...). Checking for those literals with Aho-Corasick is much cheaper
+//! than running a `RegexSet`, so clean text can skip the regex pass entirely.
+
+use aho_corasick::AhoCorasick;
+
+/// Fast literal check run before the confirming regex pass
+pub(crate) struct LiteralPrefilter {
+    literals: AhoCorasick,
+}
+
+impl LiteralPrefilter {
+    /// Build a prefilter from lowercase ASCII literals
+    ///
+    /// Every pattern guarded by the prefilter must contain at least one of
+    /// these literals, otherwise it could be skipped. `None` if the
+    /// automaton cannot be built; detectors then run the full regex pass,
+    /// which finds the same matches, only slower.
+    pub(crate) fn new(literals: &[&str]) -> Option<Self> {
+        AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(literals)
+            .ok()
+            .map(|literals| Self { literals })
+    }
+
+    /// Whether any pattern could match the text
+    ///
+    /// Non-ASCII text always passes through: `(?i)` regexes use Unicode case
+    /// folding, which an ASCII literal search cannot reproduce.
+    pub(crate) fn might_match(&self, text: &str) -> bool {
+        !text.is_ascii() || self.literals.is_match(text)
+    }
+}
+
+/// Check that every pattern mentions at least one prefilter literal
+#[cfg(test)]
+pub(crate) fn assert_literals_cover(patterns: &[&str], literals: &[&str]) {
+    for pattern in patterns {
+        let source = pattern.replace('\\', "").to_lowercase();
+        assert!(
+            literals.iter().any(|lit| source.contains(lit)),
+            "pattern {:?} has no prefilter literal",
+            pattern
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_might_match() {
+        let prefilter = LiteralPrefilter::new(&["ignore", "bypass"]).unwrap();
+        assert!(prefilter.might_match("Please IGNORE this"));
+        assert!(prefilter.might_match("bypass"));
+        assert!(!prefilter.might_match("What is the capital of France?"));
+        // Non-ASCII text is never filtered out
+        assert!(prefilter.might_match("Où est la capitale ?"));
+    }
+}
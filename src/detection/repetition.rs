@@ -0,0 +1,118 @@
+//! Repetition stuffing detection.
+//!
+//! Prompts made of one word or phrase repeated thousands of times ("a a a
+//! ...") are used to exhaust the context window or probe model limits, and
+//! they skew token estimates. Both checks here take a single pass over the
+//! whitespace-separated words of a message.
+
+use std::collections::HashSet;
+
+/// Default longest run of one word repeated back to back
+pub const DEFAULT_MAX_RUN: usize = 200;
+
+/// Default share of words in a message that repeat an earlier word
+pub const DEFAULT_MAX_RATIO: f64 = 0.95;
+
+/// Fewest words before the ratio check applies; short texts repeat words
+/// naturally, and long prose still introduces new words as it goes
+pub const MIN_WORDS_FOR_RATIO: usize = 1000;
+
+/// Word repetition in one text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Repetition {
+    /// Whitespace-separated words
+    pub words: usize,
+    /// Distinct words, compared exactly
+    pub distinct: usize,
+    /// Longest run of one word repeated back to back
+    pub longest_run: usize,
+}
+
+impl Repetition {
+    /// Measure the repetition in a text
+    pub fn measure(text: &str) -> Self {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stats = Self::default();
+        let mut previous = None;
+        let mut run = 0;
+        for word in text.split_whitespace() {
+            stats.words += 1;
+            seen.insert(word);
+            run = if previous == Some(word) { run + 1 } else { 1 };
+            stats.longest_run = stats.longest_run.max(run);
+            previous = Some(word);
+        }
+        stats.distinct = seen.len();
+        stats
+    }
+
+    /// Share of words that repeat an earlier word, 0.0 for empty text
+    pub fn ratio(&self) -> f64 {
+        if self.words == 0 {
+            return 0.0;
+        }
+        1.0 - self.distinct as f64 / self.words as f64
+    }
+
+    /// Whether the text is stuffed: a run longer than `max_run` (0 = no run
+    /// limit), or at least [`MIN_WORDS_FOR_RATIO`] words with a repetition
+    /// ratio above `max_ratio`
+    pub fn is_stuffed(&self, max_run: usize, max_ratio: f64) -> bool {
+        (max_run > 0 && self.longest_run > max_run)
+            || (self.words >= MIN_WORDS_FOR_RATIO && self.ratio() > max_ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        let stats = Repetition::measure("the cat  sat\non the the mat");
+        assert_eq!(
+            stats,
+            Repetition {
+                words: 7,
+                distinct: 5,
+                longest_run: 2,
+            }
+        );
+        assert_eq!(Repetition::measure("").ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_repeated_word_is_stuffed() {
+        let text = "a ".repeat(100_000);
+        let stats = Repetition::measure(&text);
+        assert_eq!(stats.longest_run, 100_000);
+        assert!(stats.is_stuffed(DEFAULT_MAX_RUN, DEFAULT_MAX_RATIO));
+        // The ratio check alone catches it too
+        assert!(stats.is_stuffed(0, DEFAULT_MAX_RATIO));
+    }
+
+    #[test]
+    fn test_repeated_phrase_is_stuffed() {
+        let text = "please answer now ".repeat(2_000);
+        let stats = Repetition::measure(&text);
+        assert_eq!(stats.longest_run, 1);
+        assert!(stats.is_stuffed(DEFAULT_MAX_RUN, DEFAULT_MAX_RATIO));
+    }
+
+    #[test]
+    fn test_prose_is_not_stuffed() {
+        let paragraph = "Summarize the quarterly report for the board. Focus on revenue, \
+                         churn and hiring, and flag anything that changed since the last \
+                         quarter. Keep it under one page and use plain language.";
+        let stats = Repetition::measure(paragraph);
+        assert!(!stats.is_stuffed(DEFAULT_MAX_RUN, DEFAULT_MAX_RATIO));
+
+        // Long documents repeat common words but keep adding new ones
+        let document: String = (0..3_000)
+            .map(|i| format!("the item {} was shipped to the customer. ", i))
+            .collect();
+        let stats = Repetition::measure(&document);
+        assert!(stats.ratio() < DEFAULT_MAX_RATIO);
+        assert!(!stats.is_stuffed(DEFAULT_MAX_RUN, DEFAULT_MAX_RATIO));
+    }
+}
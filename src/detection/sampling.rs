@@ -0,0 +1,74 @@
+//! Sampling parameter abuse detection.
+//!
+//! Some jailbreaks leave the prompt alone and tune sampling instead: `logit_bias`
+//! can ban the tokens a model uses to refuse, and `stop` sequences can cut a
+//! response off right before its safety disclaimer.
+
+use std::collections::HashMap;
+
+/// Stop sequences that commonly open a refusal or safety disclaimer
+pub const DEFAULT_STOP_MARKERS: &[&str] = &[
+    "I'm sorry",
+    "I am sorry",
+    "I cannot",
+    "I can't",
+    "I apologize",
+    "As an AI",
+    "Disclaimer",
+    "I must warn",
+    "Please note",
+];
+
+/// Check whether `logit_bias` touches any of the given token IDs
+///
+/// Returns the first targeted token ID found (lowest ID first).
+pub fn logit_bias_target(logit_bias: &HashMap<u32, f32>, token_ids: &[u32]) -> Option<u32> {
+    let mut targeted: Vec<u32> = logit_bias
+        .keys()
+        .copied()
+        .filter(|id| token_ids.contains(id))
+        .collect();
+    targeted.sort_unstable();
+    targeted.first().copied()
+}
+
+/// Check whether any stop sequence contains a safety-bypass marker
+///
+/// Matching is case-insensitive. Returns the first matching stop sequence.
+pub fn stop_sequence_marker<'a>(stop: &'a [String], markers: &[String]) -> Option<&'a str> {
+    let markers: Vec<String> = markers.iter().map(|m| m.to_lowercase()).collect();
+    stop.iter()
+        .find(|s| {
+            let lower = s.to_lowercase();
+            markers
+                .iter()
+                .any(|m| !m.is_empty() && lower.contains(m.as_str()))
+        })
+        .map(|s| s.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers() -> Vec<String> {
+        DEFAULT_STOP_MARKERS.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_logit_bias_target() {
+        let bias = HashMap::from([(40, -100.0), (1234, 5.0)]);
+        assert_eq!(logit_bias_target(&bias, &[1234, 40]), Some(40));
+        assert_eq!(logit_bias_target(&bias, &[99]), None);
+        assert_eq!(logit_bias_target(&HashMap::new(), &[40]), None);
+    }
+
+    #[test]
+    fn test_stop_sequence_marker() {
+        let stop = vec!["\n\n".to_string(), "i'm SORRY".to_string()];
+        assert_eq!(stop_sequence_marker(&stop, &markers()), Some("i'm SORRY"));
+
+        let stop = vec!["\n\nHuman:".to_string(), "END".to_string()];
+        assert_eq!(stop_sequence_marker(&stop, &markers()), None);
+    }
+}
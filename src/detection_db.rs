@@ -0,0 +1,351 @@
+//! SQLite persistence of detection results.
+//!
+//! Writes one row per processed request to a local database for teams
+//! without a SIEM. Requests never wait on the disk: rows are queued to a
+//! dedicated writer thread, which inserts them in batches, one transaction
+//! per batch. When the queue is full, rows are dropped and counted.
+
+use crate::{AnalysisResult, GatewayError};
+use rusqlite::{params, Connection};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Rows queued for the writer before new ones are dropped
+pub const QUEUE_CAPACITY: usize = 10_000;
+
+/// Most rows inserted in one transaction
+const MAX_BATCH: usize = 512;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS detections (
+    id INTEGER PRIMARY KEY,
+    timestamp_ms INTEGER NOT NULL,
+    correlation_id TEXT,
+    provider TEXT NOT NULL,
+    model TEXT,
+    decision TEXT NOT NULL,
+    reason_codes TEXT NOT NULL,
+    estimated_tokens INTEGER NOT NULL,
+    estimated_cost REAL NOT NULL,
+    decision_id TEXT
+);
+CREATE INDEX IF NOT EXISTS detections_timestamp ON detections (timestamp_ms);";
+
+/// Databases created before decision IDs were recorded lack the column
+const ADD_DECISION_ID: &str = "ALTER TABLE detections ADD COLUMN decision_id TEXT";
+
+const DECISION_ID_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS detections_decision_id ON detections (decision_id)";
+
+/// One processed request, as stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionRow {
+    /// Unix time in milliseconds when the decision was made
+    pub timestamp_ms: i64,
+    /// `None` for requests analyzed through the library API
+    pub correlation_id: Option<String>,
+    /// The `X-AI-Gateway-Decision-Id` returned for the request
+    pub decision_id: String,
+    pub provider: String,
+    pub model: Option<String>,
+    pub decision: String,
+    /// Reason codes, comma-separated
+    pub reason_codes: String,
+    /// Estimated prompt tokens
+    pub estimated_tokens: u32,
+    pub estimated_cost: f64,
+}
+
+impl DetectionRow {
+    /// Row for an analysis made now
+    pub fn new(correlation_id: Option<&str>, result: &AnalysisResult) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        Self {
+            timestamp_ms,
+            correlation_id: correlation_id.map(str::to_string),
+            decision_id: result.decision_id.clone(),
+            provider: result.provider.as_str().to_string(),
+            model: result.model.clone(),
+            decision: result.decision.as_str().to_string(),
+            reason_codes: result.reason_codes.join(","),
+            estimated_tokens: result.estimated_tokens,
+            estimated_cost: result.estimated_cost,
+        }
+    }
+}
+
+enum Message {
+    Row(DetectionRow),
+    /// Reply once every row queued before this one is committed
+    Flush(mpsc::Sender<()>),
+}
+
+/// Handle to the database and its writer thread
+///
+/// Dropping the handle commits the queued rows and stops the writer.
+pub struct DetectionDb {
+    path: String,
+    sender: Option<SyncSender<Message>>,
+    writer: Option<JoinHandle<()>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for DetectionDb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DetectionDb")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DetectionDb {
+    /// Open (or create) the database at `path`, create the schema, and start
+    /// the writer
+    pub fn open(path: &str) -> Result<Self, GatewayError> {
+        let conn = Connection::open(path)
+            .and_then(|conn| create_schema(&conn).map(|_| conn))
+            .map_err(|e| GatewayError::DetectionDb(format!("{}: {}", path, e)))?;
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let writer = std::thread::Builder::new()
+            .name("detection-db".to_string())
+            .spawn(move || write_rows(conn, receiver))
+            .map_err(|e| GatewayError::DetectionDb(e.to_string()))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            sender: Some(sender),
+            writer: Some(writer),
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Path the database was opened at
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Queue a row for an analyzed request; never blocks
+    pub fn record(&self, correlation_id: Option<&str>, result: &AnalysisResult) {
+        let Some(ref sender) = self.sender else {
+            return;
+        };
+        let row = DetectionRow::new(correlation_id, result);
+        match sender.try_send(Message::Row(row)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                // Warn once; `dropped` counts the rest
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!(path = %self.path, "Detection database queue full, dropping rows");
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Rows dropped because the writer fell behind or stopped
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait until every row queued so far is committed
+    pub fn flush(&self) {
+        let Some(ref sender) = self.sender else {
+            return;
+        };
+        let (done, wait) = mpsc::channel();
+        if sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+impl Drop for DetectionDb {
+    fn drop(&mut self) {
+        // Closing the queue lets the writer commit what is left and exit
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn create_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)?;
+    let has_decision_id = conn
+        .prepare("SELECT 1 FROM pragma_table_info('detections') WHERE name = 'decision_id'")?
+        .exists([])?;
+    if !has_decision_id {
+        conn.execute_batch(ADD_DECISION_ID)?;
+    }
+    conn.execute_batch(DECISION_ID_INDEX)
+}
+
+/// Writer loop: block for one message, then take whatever else is queued
+fn write_rows(mut conn: Connection, receiver: Receiver<Message>) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    let mut flushes = Vec::new();
+    while let Ok(first) = receiver.recv() {
+        let mut next = Some(first);
+        while let Some(message) = next {
+            match message {
+                Message::Row(row) => batch.push(row),
+                Message::Flush(done) => flushes.push(done),
+            }
+            next = if batch.len() < MAX_BATCH {
+                receiver.try_recv().ok()
+            } else {
+                None
+            };
+        }
+
+        if let Err(e) = insert_batch(&mut conn, &batch) {
+            warn!(rows = batch.len(), error = %e, "Failed to write detection rows");
+        } else {
+            debug!(rows = batch.len(), "Wrote detection rows");
+        }
+        batch.clear();
+        for done in flushes.drain(..) {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn insert_batch(conn: &mut Connection, rows: &[DetectionRow]) -> rusqlite::Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let tx = conn.transaction()?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT INTO detections (timestamp_ms, correlation_id, provider, model, decision,
+                reason_codes, estimated_tokens, estimated_cost, decision_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+        for row in rows {
+            insert.execute(params![
+                row.timestamp_ms,
+                row.correlation_id,
+                row.provider,
+                row.model,
+                row.decision,
+                row.reason_codes,
+                row.estimated_tokens,
+                row.estimated_cost,
+                row.decision_id,
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::AiProvider;
+    use crate::AnalysisDecision;
+
+    #[test]
+    fn test_rows_are_written_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("detections.db");
+        let path = path.to_str().unwrap();
+        let db = DetectionDb::open(path).unwrap();
+
+        let mut result = AnalysisResult::new(AiProvider::Anthropic);
+        result.model = Some("claude-3".to_string());
+        result.decision = AnalysisDecision::RateLimited;
+        result.reason_codes = vec!["RATE_LIMIT_EXCEEDED".to_string()];
+        db.record(Some("req-1"), &result);
+        db.record(None, &AnalysisResult::new(AiProvider::OpenAI));
+        db.flush();
+
+        let conn = Connection::open(path).unwrap();
+        let rows: Vec<(Option<String>, String, String, String)> = conn
+            .prepare("SELECT correlation_id, provider, decision, reason_codes FROM detections ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    Some("req-1".to_string()),
+                    "anthropic".to_string(),
+                    "rate-limited".to_string(),
+                    "RATE_LIMIT_EXCEEDED".to_string()
+                ),
+                (
+                    None,
+                    "openai".to_string(),
+                    "allow".to_string(),
+                    String::new()
+                ),
+            ]
+        );
+        assert_eq!(db.dropped(), 0);
+    }
+
+    #[test]
+    fn test_old_database_gains_decision_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("detections.db");
+        let path = path.to_str().unwrap();
+        Connection::open(path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE detections (
+                    id INTEGER PRIMARY KEY,
+                    timestamp_ms INTEGER NOT NULL,
+                    correlation_id TEXT,
+                    provider TEXT NOT NULL,
+                    model TEXT,
+                    decision TEXT NOT NULL,
+                    reason_codes TEXT NOT NULL,
+                    estimated_tokens INTEGER NOT NULL,
+                    estimated_cost REAL NOT NULL
+                )",
+            )
+            .unwrap();
+
+        let db = DetectionDb::open(path).unwrap();
+        let result = AnalysisResult::new(AiProvider::OpenAI);
+        db.record(Some("req-1"), &result);
+        db.flush();
+
+        let conn = Connection::open(path).unwrap();
+        let decision_id: String = conn
+            .query_row("SELECT decision_id FROM detections", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(decision_id, result.decision_id);
+    }
+
+    #[test]
+    fn test_reopen_keeps_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("detections.db");
+        let path = path.to_str().unwrap();
+
+        // Dropping the handle commits what is queued
+        let db = DetectionDb::open(path).unwrap();
+        db.record(Some("req-1"), &AnalysisResult::new(AiProvider::OpenAI));
+        drop(db);
+
+        let db = DetectionDb::open(path).unwrap();
+        db.flush();
+        let conn = Connection::open(path).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM detections", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,116 @@
+//! Error type for the agent's fallible public functions.
+//!
+//! Messages match what operators already see in logs and CLI warnings; the
+//! variants let library users tell a bad config value from a failed
+//! moderation call without matching on text.
+
+use crate::CheckKind;
+
+/// Errors returned by config parsing, detection, and transport setup
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GatewayError {
+    /// A config value that is not one of the accepted names
+    #[error("Invalid {field}: {value}")]
+    InvalidConfigValue {
+        /// Human-readable name of the setting, e.g. `PII action`
+        field: &'static str,
+        /// The value as given
+        value: String,
+    },
+
+    /// `check-order` names the same check twice
+    #[error("check-order lists {0:?} more than once")]
+    DuplicateCheck(CheckKind),
+
+    /// A config value outside its allowed range, or a config that does not parse
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// A problem in one tenant's config
+    #[error("tenant {tenant}: {error}")]
+    Tenant {
+        /// Tenant ID, as keyed in `tenant-configs`
+        tenant: String,
+        /// What is wrong with its config
+        error: Box<GatewayError>,
+    },
+
+    /// An IP address or CIDR range that does not parse
+    #[error("Invalid IP network: {0}")]
+    InvalidNetwork(String),
+
+    /// The moderation endpoint could not be reached or timed out
+    #[error("moderation request failed: {0}")]
+    ModerationRequest(String),
+
+    /// The moderation endpoint answered with a non-2xx status
+    #[error("moderation endpoint returned {0}")]
+    ModerationStatus(String),
+
+    /// The moderation response was not JSON or carried no verdict
+    #[error("invalid moderation response: {0}")]
+    ModerationResponse(String),
+
+    /// A listen address that does not parse
+    #[error("Invalid gRPC address '{address}': {reason}")]
+    InvalidListenAddress {
+        /// The address as given
+        address: String,
+        /// Why it was rejected
+        reason: String,
+    },
+
+    /// A built-in detection pattern that does not compile
+    #[error("{detector} pattern {pattern:?} does not compile: {reason}")]
+    InvalidPattern {
+        /// Detector the pattern belongs to, e.g. `prompt-injection`
+        detector: &'static str,
+        /// The pattern source
+        pattern: String,
+        /// The compiler's error
+        reason: String,
+    },
+
+    /// The detection database could not be opened or initialized
+    #[error("detection database: {0}")]
+    DetectionDb(String),
+
+    /// A transport this platform cannot serve
+    #[error("{0}")]
+    UnsupportedTransport(String),
+
+    /// A detector registered while abandoned scans still hold the detectors
+    #[error("detectors are in use by scans still in flight")]
+    DetectorsInUse,
+}
+
+impl GatewayError {
+    /// Shorthand for [`GatewayError::InvalidConfigValue`]
+    pub(crate) fn invalid_value(field: &'static str, value: &str) -> Self {
+        GatewayError::InvalidConfigValue {
+            field,
+            value: value.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_match_previous_strings() {
+        assert_eq!(
+            GatewayError::invalid_value("PII action", "nope").to_string(),
+            "Invalid PII action: nope"
+        );
+        assert_eq!(
+            GatewayError::DuplicateCheck(CheckKind::Pii).to_string(),
+            "check-order lists Pii more than once"
+        );
+        assert_eq!(
+            GatewayError::ModerationStatus("503 Service Unavailable".to_string()).to_string(),
+            "moderation endpoint returned 503 Service Unavailable"
+        );
+    }
+}
@@ -2,10 +2,18 @@
 
 use anyhow::Result;
 use clap::Parser;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use tracing::info;
 use tracing_subscriber::{fmt, EnvFilter};
-use zentinel_agent_ai_gateway::{AiGatewayAgent, AiGatewayConfig, PiiAction};
-use zentinel_agent_protocol::v2::{GrpcAgentServerV2, UdsAgentServerV2};
+use zentinel_agent_ai_gateway::{
+    stats, AiGatewayAgent, AiGatewayConfig, AiGatewayConfigJson, CheckKind, ConfigParseFailureMode,
+    DetectorAction, FailureClass, GatewayError, PiiAction, PiiScanScope, RateLimitResetFormat,
+    RealtimePolicy, SampledScan, SharedAiGatewayAgent, TlsVersion, TokenLimitTarget,
+};
+use zentinel_agent_protocol::v2::GrpcAgentServerV2;
+#[cfg(unix)]
+use zentinel_agent_protocol::v2::UdsAgentServerV2;
 
 /// AI Gateway Agent for Zentinel proxy
 ///
@@ -16,22 +24,38 @@ use zentinel_agent_protocol::v2::{GrpcAgentServerV2, UdsAgentServerV2};
 #[command(version, about, long_about = None)]
 struct Args {
     /// Unix socket path for agent communication (UDS transport)
-    #[arg(
-        long,
-        env = "AGENT_SOCKET",
-        default_value = "/tmp/zentinel-ai-gateway.sock"
-    )]
-    socket: String,
+    ///
+    /// Defaults to /tmp/zentinel-ai-gateway.sock when --grpc-address is not set.
+    /// Setting both serves UDS and gRPC at once from one agent. Not available
+    /// on Windows, where the agent defaults to gRPC on 127.0.0.1:50051.
+    #[arg(long, env = "AGENT_SOCKET")]
+    socket: Option<String>,
 
     /// gRPC address for agent communication (e.g., 0.0.0.0:50051)
-    /// When specified, the agent will use gRPC transport instead of UDS
+    /// When specified without --socket, the agent will use gRPC transport instead of UDS
     #[arg(long, env = "GRPC_ADDRESS")]
     grpc_address: Option<String>,
 
+    /// Address for the JSON detection stats endpoint (GET /stats), e.g. 127.0.0.1:9464
+    #[arg(long, env = "STATS_ADDRESS")]
+    stats_address: Option<String>,
+
     /// Enable prompt injection detection
     #[arg(long, env = "PROMPT_INJECTION", default_value = "true")]
     prompt_injection: bool,
 
+    /// Enable stricter detection categories (delimiter-framed injection)
+    #[arg(long, env = "STRICT_MODE", default_value = "false")]
+    strict_mode: bool,
+
+    /// Check translated instruction-override phrases (higher false-positive rate)
+    #[arg(long, env = "MULTILINGUAL_DETECTION", default_value = "false")]
+    multilingual_detection: bool,
+
+    /// Comma-separated languages for multilingual detection
+    #[arg(long, env = "MULTILINGUAL_LANGUAGES", default_value = "es,fr,de,it,pt")]
+    multilingual_languages: String,
+
     /// Enable PII detection
     #[arg(long, env = "PII_DETECTION", default_value = "true")]
     pii_detection: bool,
@@ -40,6 +64,46 @@ struct Args {
     #[arg(long, env = "PII_ACTION", default_value = "log")]
     pii_action: String,
 
+    /// Content scanned for PII: all, user-only, exclude-system
+    #[arg(long, env = "PII_SCAN_SCOPE", default_value = "all")]
+    pii_scan_scope: String,
+
+    /// Block response body when PII blocks a request; {types} lists the detected types
+    #[arg(long, env = "PII_BLOCK_MESSAGE_TEMPLATE")]
+    pii_block_message_template: Option<String>,
+
+    /// Minimum confidence (0.0-1.0) for phone/card matches to count as PII
+    #[arg(long, env = "PII_MIN_CONFIDENCE", default_value = "0.0")]
+    pii_min_confidence: f32,
+
+    /// Distinct PII values a request must contain before --pii-action applies
+    #[arg(long, env = "PII_MIN_MATCHES", default_value = "1")]
+    pii_min_matches: usize,
+
+    /// Comma-separated email domains that don't count as PII
+    #[arg(long, env = "PII_EMAIL_DOMAIN_ALLOWLIST", default_value = "")]
+    pii_email_domain_allowlist: String,
+
+    /// Report well-known test card numbers as credit cards
+    #[arg(long, env = "ALLOW_TEST_CARDS", default_value = "true")]
+    allow_test_cards: bool,
+
+    /// Also scan messages with spaced-out letters and markdown emphasis removed
+    #[arg(long, env = "NORMALIZE_OBFUSCATION", default_value = "false")]
+    normalize_obfuscation: bool,
+
+    /// Tag data: URIs and large base64 blobs pasted into message text
+    #[arg(long, env = "DATA_URI_DETECTION", default_value = "false")]
+    data_uri_detection: bool,
+
+    /// Block requests with inline data: URIs or base64 blobs
+    #[arg(long, env = "BLOCK_DATA_URIS", default_value = "false")]
+    block_data_uris: bool,
+
+    /// Length at which a base64 run in message text counts as a blob (0 = ignore)
+    #[arg(long, env = "MAX_INLINE_BASE64_CHARS", default_value = "4096")]
+    max_inline_base64_chars: usize,
+
     /// Enable jailbreak detection
     #[arg(long, env = "JAILBREAK_DETECTION", default_value = "true")]
     jailbreak_detection: bool,
@@ -52,14 +116,118 @@ struct Args {
     #[arg(long, env = "ALLOWED_MODELS", default_value = "")]
     allowed_models: String,
 
-    /// Maximum tokens per request (0 = no limit)
+    /// Match allowed models case-insensitively
+    #[arg(long, env = "MODEL_MATCH_CASE_INSENSITIVE", default_value = "true")]
+    model_match_case_insensitive: bool,
+
+    /// Maximum requested output tokens (max_tokens) per request (0 = no limit)
     #[arg(long, env = "MAX_TOKENS", default_value = "0")]
     max_tokens: u32,
 
+    /// What --max-tokens checks: declared-output, estimated-prompt, or both
+    #[arg(long, env = "TOKEN_LIMIT_TARGET", default_value = "declared-output")]
+    token_limit_target: String,
+
+    /// What to do with a pushed config that does not parse: keep-current, use-defaults, or reject
+    #[arg(
+        long,
+        env = "CONFIG_PARSE_FAILURE_MODE",
+        default_value = "keep-current"
+    )]
+    config_parse_failure_mode: String,
+
+    /// Maximum estimated prompt tokens across all messages (0 = no limit)
+    #[arg(long, env = "MAX_PROMPT_TOKENS", default_value = "0")]
+    max_prompt_tokens: u32,
+
+    /// Lowest client TLS version accepted, e.g. TLSv1.3 (unset = any, including plaintext)
+    #[arg(long, env = "MIN_TLS_VERSION")]
+    min_tls_version: Option<String>,
+
+    /// SQLite file receiving one row per processed request (needs the sqlite feature)
+    #[arg(long, env = "DETECTION_DB_PATH")]
+    detection_db_path: Option<String>,
+
+    /// OpenAI Realtime API WebSocket sessions: allow (unscanned) or block
+    #[arg(long, env = "REALTIME_POLICY", default_value = "allow")]
+    realtime_policy: String,
+
+    /// Comma-separated model aliases mapped to canonical names, e.g. gpt-4-0613=gpt-4
+    #[arg(long, env = "MODEL_ALIASES", default_value = "")]
+    model_aliases: String,
+
+    /// Block requests that do not name a model (MODEL_REQUIRED)
+    #[arg(long, env = "REQUIRE_MODEL", default_value = "false")]
+    require_model: bool,
+
+    /// Model assumed for the allowlist, pricing, and per-model limits when a request names none
+    #[arg(long, env = "DEFAULT_MODEL")]
+    default_model: Option<String>,
+
+    /// Action on prompt injection: block or log (unset = follow --block-mode)
+    #[arg(long, env = "INJECTION_ACTION")]
+    injection_action: Option<String>,
+
+    /// Action on jailbreak attempts: block or log (unset = follow --block-mode)
+    #[arg(long, env = "JAILBREAK_ACTION")]
+    jailbreak_action: Option<String>,
+
+    /// Flag invisible Unicode (tag characters, zero-width spaces) and scan the text it hides
+    #[arg(long, env = "DETECT_HIDDEN_UNICODE", default_value = "true")]
+    detect_hidden_unicode: bool,
+
+    /// Add X-AI-Gateway-PII-Counts with distinct match counts (not values) per PII type
+    #[arg(long, env = "PII_COUNTS_HEADER", default_value = "false")]
+    pii_counts_header: bool,
+
+    /// Comma-separated model context windows in tokens, e.g. gpt-4=8192,claude-3=200000
+    #[arg(long, env = "CONTEXT_WINDOWS", default_value = "")]
+    context_windows: String,
+
+    /// Per-model token limits overriding --max-tokens, e.g. gpt-4=4096,gpt-3.5=16384
+    #[arg(long, env = "PER_MODEL_TOKEN_LIMITS", default_value = "")]
+    per_model_token_limits: String,
+
+    /// Fraction of the context window (0.0-1.0) above which requests are flagged CONTEXT_NEAR_LIMIT
+    #[arg(long, env = "CONTEXT_WARN_FRACTION", default_value = "0.9")]
+    context_warn_fraction: f64,
+
+    /// Block prompts estimated to fill the model's whole context window
+    #[arg(long, env = "BLOCK_CONTEXT_OVERFLOW", default_value = "false")]
+    block_context_overflow: bool,
+
+    /// Highest sampling temperature a request may ask for
+    #[arg(long, env = "MAX_TEMPERATURE")]
+    max_temperature: Option<f32>,
+
+    /// Highest top_p a request may ask for
+    #[arg(long, env = "MAX_TOP_P")]
+    max_top_p: Option<f32>,
+
+    /// Per-request time budget for content detectors in milliseconds (0 = unbounded)
+    #[arg(long, env = "SCAN_TIMEOUT_MS", default_value = "0")]
+    scan_timeout_ms: u64,
+
+    /// Overall time budget for inspecting one request in milliseconds (0 = unbounded)
+    #[arg(long, env = "REQUEST_TIMEOUT_MS", default_value = "0")]
+    request_timeout_ms: u64,
+
+    /// Maximum estimated tokens in a provider response (0 = no limit)
+    #[arg(long, env = "MAX_RESPONSE_TOKENS", default_value = "0")]
+    max_response_tokens: u32,
+
+    /// Maximum characters in a single message (0 = no limit)
+    #[arg(long, env = "MAX_MESSAGE_CHARS", default_value = "0")]
+    max_message_chars: usize,
+
     /// Add cost estimation headers
     #[arg(long, env = "ADD_COST_HEADERS", default_value = "true")]
     add_cost_headers: bool,
 
+    /// Send the cost header for models without a known price (fallback rate)
+    #[arg(long, env = "COST_HEADERS_FOR_UNPRICED", default_value = "true")]
+    cost_headers_for_unpriced: bool,
+
     /// Block mode (false = detect-only, log but don't block)
     #[arg(long, env = "BLOCK_MODE", default_value = "true")]
     block_mode: bool,
@@ -68,6 +236,14 @@ struct Args {
     #[arg(long, env = "FAIL_OPEN", default_value = "false")]
     fail_open: bool,
 
+    /// Override --fail-open for internal errors (e.g. undecodable body chunks)
+    #[arg(long, env = "FAIL_OPEN_ON_ERROR")]
+    fail_open_on_error: Option<bool>,
+
+    /// Override --fail-open for malformed request bodies (e.g. invalid UTF-8)
+    #[arg(long, env = "FAIL_OPEN_ON_PARSE")]
+    fail_open_on_parse: Option<bool>,
+
     /// Rate limit: requests per minute per client (0 = unlimited)
     #[arg(long, env = "RATE_LIMIT_REQUESTS", default_value = "0")]
     rate_limit_requests: u32,
@@ -76,23 +252,391 @@ struct Args {
     #[arg(long, env = "RATE_LIMIT_TOKENS", default_value = "0")]
     rate_limit_tokens: u32,
 
+    /// Rate limit: requests a client may burst beyond the per-window limit (0 = none)
+    #[arg(long, env = "RATE_LIMIT_BURST", default_value = "0")]
+    rate_limit_burst: u32,
+
+    /// Rate limit each client separately per provider (OpenAI, Anthropic, ...)
+    #[arg(long, env = "RATE_LIMIT_PER_PROVIDER", default_value = "false")]
+    rate_limit_per_provider: bool,
+
+    /// Global rate limit: requests per window across all clients (0 = unlimited)
+    #[arg(long, env = "GLOBAL_RATE_LIMIT_REQUESTS", default_value = "0")]
+    global_rate_limit_requests: u32,
+
+    /// Global rate limit: tokens per window across all clients (0 = unlimited)
+    #[arg(long, env = "GLOBAL_RATE_LIMIT_TOKENS", default_value = "0")]
+    global_rate_limit_tokens: u32,
+
+    /// Token estimate multiplier for message text (~4 characters per token at 1.0)
+    #[arg(long, env = "TOKEN_WEIGHT_TEXT", default_value = "1.0")]
+    token_weight_text: f32,
+
+    /// Token estimate multiplier for tool definitions and tool-call JSON
+    #[arg(long, env = "TOKEN_WEIGHT_TOOL_JSON", default_value = "1.3")]
+    token_weight_tool_json: f32,
+
+    /// Estimated tokens per image reference
+    #[arg(long, env = "IMAGE_TOKEN_COST", default_value = "765")]
+    image_token_cost: u32,
+
+    /// Rate limit window length in seconds
+    #[arg(long, env = "RATE_LIMIT_WINDOW_SECONDS", default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    rate_limit_window_seconds: u64,
+
+    /// Comma-separated client IPs or CIDR networks exempt from rate limiting
+    #[arg(long, env = "RATE_LIMIT_BYPASS_IPS", default_value = "")]
+    rate_limit_bypass_ips: String,
+
+    /// X-RateLimit-Reset format: seconds (until reset) or epoch (Unix timestamp)
+    #[arg(long, env = "RATELIMIT_RESET_FORMAT", default_value = "seconds")]
+    ratelimit_reset_format: String,
+
+    /// Comma-separated HTTP methods whose bodies are scanned
+    #[arg(long, env = "SCANNED_METHODS", default_value = "POST,PUT,PATCH")]
+    scanned_methods: String,
+
+    /// Comma-separated message roles to scan with detectors (empty = all roles)
+    #[arg(long, env = "SCAN_ROLES", default_value = "user,system")]
+    scan_roles: String,
+
+    /// Leave the leading system prompt out of injection, jailbreak, and other content checks (trusts client-supplied text; only for clients that cannot set it)
+    #[arg(long, env = "TRUST_SYSTEM_PROMPT", default_value = "false")]
+    trust_system_prompt: bool,
+
+    /// Scan image URL fields in vision requests
+    #[arg(long, env = "SCAN_IMAGE_METADATA", default_value = "false")]
+    scan_image_metadata: bool,
+
+    /// Flag requests using logit_bias or stop sequences to suppress safety output
+    #[arg(long, env = "DETECT_SAMPLING_ABUSE", default_value = "false")]
+    detect_sampling_abuse: bool,
+
+    /// Comma-separated token IDs that logit_bias must not target (e.g. refusal tokens)
+    #[arg(long, env = "SAMPLING_ABUSE_TOKEN_IDS", default_value = "")]
+    sampling_abuse_token_ids: String,
+
+    /// Flag messages made of one word or phrase repeated over and over
+    #[arg(long, env = "DETECT_REPETITION_STUFFING", default_value = "false")]
+    detect_repetition_stuffing: bool,
+
+    /// Longest run of one word repeated back to back (0 = no run limit)
+    #[arg(long, env = "REPETITION_MAX_RUN", default_value = "200")]
+    repetition_max_run: usize,
+
+    /// Share of words repeating an earlier word (0.0-1.0) above which a message of 1000+ words is stuffed
+    #[arg(long, env = "REPETITION_MAX_RATIO", default_value = "0.95")]
+    repetition_max_ratio: f64,
+
+    /// Policy revision reported in X-AI-Gateway-Policy-Version and audit metadata
+    #[arg(long, env = "POLICY_VERSION")]
+    policy_version: Option<String>,
+
+    /// Namespace tag leading every audit tag list (e.g. ai-gw-prod)
+    #[arg(long, env = "AUDIT_TAG_PREFIX", default_value = "ai-gateway")]
+    audit_tag_prefix: String,
+
+    /// Unpack and scan chat messages embedded as JSON inside message content
+    #[arg(long, env = "DETECT_NESTED_PROMPTS", default_value = "false")]
+    detect_nested_prompts: bool,
+
+    /// Report prompt injections inside fenced code blocks as injection:in-code
+    #[arg(long, env = "DETECT_INJECTION_IN_CODE", default_value = "false")]
+    detect_injection_in_code: bool,
+
+    /// Action on injections found only in code: block or log (unset = follow --injection-action; instruction overrides always do)
+    #[arg(long, env = "INJECTION_IN_CODE_ACTION")]
+    injection_in_code_action: Option<String>,
+
+    /// Scan a trailing assistant message (prefill) for injection, reported as assistant-prefill-injection
+    #[arg(long, env = "SCAN_ASSISTANT_PREFILL", default_value = "false")]
+    scan_assistant_prefill: bool,
+
+    /// Scan each body chunk for injection and jailbreaks as it arrives, blocking before the body is complete
+    #[arg(long, env = "INCREMENTAL_SCAN", default_value = "false")]
+    incremental_scan: bool,
+
+    /// External moderation endpoint called before allowing a request
+    #[arg(long, env = "EXTERNAL_MODERATION_URL")]
+    external_moderation_url: Option<String>,
+
+    /// Bearer token for the external moderation endpoint
+    #[arg(long, env = "EXTERNAL_MODERATION_API_KEY", hide_env_values = true)]
+    external_moderation_api_key: Option<String>,
+
+    /// Time budget for one external moderation call in milliseconds
+    #[arg(long, env = "EXTERNAL_MODERATION_TIMEOUT_MS", default_value = "2000")]
+    external_moderation_timeout_ms: u64,
+
+    /// Comma-separated keywords that block a request (case-insensitive substrings)
+    #[arg(long, env = "BLOCKED_KEYWORDS", default_value = "")]
+    blocked_keywords: String,
+
+    /// Keep matched blocked keywords out of logs, tags, and block reasons
+    #[arg(long, env = "BLOCKED_KEYWORDS_SENSITIVE", default_value = "false")]
+    blocked_keywords_sensitive: bool,
+
+    /// Comma-separated headers every request must carry, e.g. x-tenant-id,authorization
+    #[arg(long, env = "REQUIRED_HEADERS", default_value = "")]
+    required_headers: String,
+
+    /// Comma-separated correlation-ID prefixes of internal traffic to skip, e.g. healthcheck-
+    #[arg(long, env = "EXEMPT_CORRELATION_PREFIXES", default_value = "")]
+    exempt_correlation_prefixes: String,
+
+    /// Comma-separated extra headers to redact in logs (authorization, cookie, and other credential headers always are)
+    #[arg(long, env = "SENSITIVE_HEADERS", default_value = "")]
+    sensitive_headers: String,
+
+    /// Comma-separated client headers to remove from allowed requests, e.g. x-debug
+    #[arg(long, env = "STRIP_REQUEST_HEADERS", default_value = "")]
+    strip_request_headers: String,
+
+    /// Scan every string value in the request JSON for PII, not just message content
+    #[arg(long, env = "PII_DEEP_SCAN", default_value = "false")]
+    pii_deep_scan: bool,
+
+    /// Comma-separated check order, e.g. pii,prompt-injection (unlisted checks follow in the default order)
+    #[arg(long, env = "CHECK_ORDER", default_value = "")]
+    check_order: String,
+
+    /// Entries in the scan result cache for identical request content (0 = disabled)
+    #[arg(long, env = "SCAN_CACHE_SIZE", default_value = "0")]
+    scan_cache_size: usize,
+
+    /// How long cached scan results are reused, in seconds
+    #[arg(long, env = "SCAN_CACHE_TTL_SECONDS", default_value = "30")]
+    scan_cache_ttl_seconds: u64,
+
+    /// Fraction of requests (0.0-1.0) that run the sampled expensive scans
+    #[arg(long, env = "SCAN_SAMPLE_RATE", default_value = "1.0")]
+    scan_sample_rate: f64,
+
+    /// Comma-separated scans subject to the sample rate (external-moderation, nested-prompts, custom-detectors)
+    #[arg(
+        long,
+        env = "SCAN_SAMPLED_CHECKS",
+        default_value = "external-moderation,nested-prompts"
+    )]
+    scan_sampled_checks: String,
+
     /// Enable verbose debug logging
     #[arg(long, short, env = "VERBOSE", default_value = "false")]
     verbose: bool,
+
+    /// Print the effective configuration as JSON and exit without starting the agent
+    #[arg(long)]
+    dump_effective_config: bool,
+
+    /// Validate a JSON config file (the format pushed by the proxy) and exit; nonzero on errors
+    #[arg(long, value_name = "PATH")]
+    validate_config: Option<std::path::PathBuf>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+/// Socket path used when no transport is configured
+#[cfg(unix)]
+const DEFAULT_SOCKET: &str = "/tmp/zentinel-ai-gateway.sock";
 
-    // Initialize logging
-    let filter = if args.verbose {
-        EnvFilter::new("debug")
+/// gRPC address used when no transport is configured on Windows
+#[cfg(windows)]
+const DEFAULT_GRPC_ADDRESS: &str = "127.0.0.1:50051";
+
+/// Parse a comma-separated list, dropping empty entries
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse a comma-separated list of token IDs, dropping invalid entries
+fn parse_token_ids(value: &str) -> Vec<u32> {
+    parse_list(value)
+        .iter()
+        .filter_map(|s| s.parse().ok())
+        .collect()
+}
+
+/// Parse a comma-separated check order, skipping unknown and repeated checks
+fn parse_check_order(value: &str) -> Vec<CheckKind> {
+    let mut order = Vec::new();
+    for name in parse_list(value) {
+        match name.parse::<CheckKind>() {
+            Ok(kind) if !order.contains(&kind) => order.push(kind),
+            Ok(_) => eprintln!("Warning: check '{}' listed more than once, ignoring", name),
+            Err(e) => eprintln!("Warning: {}, ignoring", e),
+        }
+    }
+    order
+}
+
+/// Parse a comma-separated list of sampled scans, skipping unknown entries
+fn parse_sampled_checks(value: &str) -> Vec<SampledScan> {
+    let mut scans = Vec::new();
+    for name in parse_list(value) {
+        match name.parse::<SampledScan>() {
+            Ok(scan) if !scans.contains(&scan) => scans.push(scan),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: {}, ignoring", e),
+        }
+    }
+    scans
+}
+
+/// Parse comma-separated `model=tokens` pairs, skipping malformed and zero entries
+fn parse_model_tokens(value: &str, what: &str) -> HashMap<String, u32> {
+    let mut windows = HashMap::new();
+    for entry in parse_list(value) {
+        match entry
+            .split_once('=')
+            .and_then(|(model, tokens)| Some((model.trim(), tokens.trim().parse().ok()?)))
+        {
+            Some((model, tokens)) if !model.is_empty() && tokens > 0 => {
+                windows.insert(model.to_string(), tokens);
+            }
+            _ => eprintln!("Warning: Invalid {}: {}, ignoring", what, entry),
+        }
+    }
+    windows
+}
+
+/// Parse comma-separated `alias=model` pairs, skipping malformed entries
+fn parse_model_aliases(value: &str) -> HashMap<String, String> {
+    let mut aliases = HashMap::new();
+    for entry in parse_list(value) {
+        match entry.split_once('=').map(|(a, m)| (a.trim(), m.trim())) {
+            Some((alias, model)) if !alias.is_empty() && !model.is_empty() => {
+                aliases.insert(alias.to_string(), model.to_string());
+            }
+            _ => eprintln!("Warning: Invalid model alias: {}, ignoring", entry),
+        }
+    }
+    aliases
+}
+
+/// Check a JSON config file, printing every problem found
+fn validate_config_file(path: &std::path::Path) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    let config: AiGatewayConfigJson =
+        serde_json::from_str(&text).map_err(|e| GatewayError::InvalidConfig(e.to_string()))?;
+    let errors = config.validate();
+    for e in &errors {
+        eprintln!("error: {}", e);
+    }
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "{}: {} configuration error(s)",
+            path.display(),
+            errors.len()
+        );
+    }
+    println!("{}: configuration is valid", path.display());
+    Ok(())
+}
+
+/// Parse an optional gRPC listen address
+fn parse_grpc_address(addr: Option<&str>) -> Result<Option<SocketAddr>> {
+    addr.map(|addr| {
+        addr.parse().map_err(|e: std::net::AddrParseError| {
+            GatewayError::InvalidListenAddress {
+                address: addr.to_string(),
+                reason: e.to_string(),
+            }
+            .into()
+        })
+    })
+    .transpose()
+}
+
+/// Pick the gRPC address and UDS path to serve
+///
+/// UDS stays the default transport; it is skipped only for gRPC-only setups.
+#[cfg(unix)]
+fn select_transports(args: &Args) -> Result<(Option<SocketAddr>, Option<String>)> {
+    let grpc_addr = parse_grpc_address(args.grpc_address.as_deref())?;
+    let socket = match (&args.socket, grpc_addr) {
+        (Some(socket), _) => Some(socket.clone()),
+        (None, None) => Some(DEFAULT_SOCKET.to_string()),
+        (None, Some(_)) => None,
+    };
+    Ok((grpc_addr, socket))
+}
+
+/// Pick the gRPC address to serve; Windows has no Unix domain sockets
+#[cfg(windows)]
+fn select_transports(args: &Args) -> Result<(Option<SocketAddr>, Option<String>)> {
+    if let Some(ref socket) = args.socket {
+        return Err(GatewayError::UnsupportedTransport(format!(
+            "Unix domain sockets are not supported on Windows (got --socket {}); use --grpc-address instead",
+            socket
+        ))
+        .into());
+    }
+    let addr = args.grpc_address.as_deref().unwrap_or(DEFAULT_GRPC_ADDRESS);
+    Ok((parse_grpc_address(Some(addr))?, None))
+}
+
+/// Build the agent configuration from the parsed arguments
+fn build_config(args: &Args) -> Result<AiGatewayConfig> {
+    // Fall back to sampling every request when the rate is outside 0.0-1.0
+    let scan_sample_rate = if (0.0..=1.0).contains(&args.scan_sample_rate) {
+        args.scan_sample_rate
     } else {
-        EnvFilter::new("info")
+        eprintln!(
+            "Warning: scan sample rate {} is outside 0.0-1.0, defaulting to 1.0",
+            args.scan_sample_rate
+        );
+        1.0
     };
 
-    fmt().with_env_filter(filter).with_target(false).init();
+    // Fall back to the default context warn fraction outside 0.0-1.0
+    let context_warn_fraction = if (0.0..=1.0).contains(&args.context_warn_fraction) {
+        args.context_warn_fraction
+    } else {
+        eprintln!(
+            "Warning: context warn fraction {} is outside 0.0-1.0, defaulting to 0.9",
+            args.context_warn_fraction
+        );
+        0.9
+    };
+
+    // Fall back to the default repetition ratio outside 0.0-1.0
+    let repetition_max_ratio = if (0.0..=1.0).contains(&args.repetition_max_ratio) {
+        args.repetition_max_ratio
+    } else {
+        eprintln!(
+            "Warning: repetition max ratio {} is outside 0.0-1.0, defaulting to 0.95",
+            args.repetition_max_ratio
+        );
+        0.95
+    };
+
+    // Parse the TLS floor; an unknown version must not leave it unenforced
+    let min_tls_version = args
+        .min_tls_version
+        .as_deref()
+        .map(str::parse::<TlsVersion>)
+        .transpose()?;
+
+    // Parse the realtime session policy
+    let realtime_policy: RealtimePolicy = args.realtime_policy.parse().unwrap_or_else(|e| {
+        eprintln!("Warning: {}, defaulting to 'allow'", e);
+        RealtimePolicy::Allow
+    });
+
+    // Parse per-detector actions; unset or invalid follows block mode
+    let detector_action = |value: &Option<String>| {
+        value.as_deref().and_then(|v| {
+            v.parse::<DetectorAction>()
+                .map_err(|e| eprintln!("Warning: {}, following block mode", e))
+                .ok()
+        })
+    };
+    let injection_action = detector_action(&args.injection_action);
+    let jailbreak_action = detector_action(&args.jailbreak_action);
+    let injection_in_code_action = detector_action(&args.injection_in_code_action);
 
     // Parse PII action
     let pii_action: PiiAction = args.pii_action.parse().unwrap_or_else(|e| {
@@ -100,19 +644,35 @@ async fn main() -> Result<()> {
         PiiAction::Log
     });
 
+    // Parse PII scan scope
+    let pii_scan_scope: PiiScanScope = args.pii_scan_scope.parse().unwrap_or_else(|e| {
+        eprintln!("Warning: {}, defaulting to 'all'", e);
+        PiiScanScope::All
+    });
+
+    // Parse rate limit reset format
+    let ratelimit_reset_format: RateLimitResetFormat =
+        args.ratelimit_reset_format.parse().unwrap_or_else(|e| {
+            eprintln!("Warning: {}, defaulting to 'seconds'", e);
+            RateLimitResetFormat::Seconds
+        });
+
+    // Parse token limit target
+    let token_limit_target: TokenLimitTarget =
+        args.token_limit_target.parse().unwrap_or_else(|e| {
+            eprintln!("Warning: {}, defaulting to 'declared-output'", e);
+            TokenLimitTarget::DeclaredOutput
+        });
+    let config_parse_failure_mode: ConfigParseFailureMode =
+        args.config_parse_failure_mode.parse().unwrap_or_else(|e| {
+            eprintln!("Warning: {}, defaulting to 'keep-current'", e);
+            ConfigParseFailureMode::KeepCurrent
+        });
+
     // Parse allowed models
-    let allowed_models: Vec<String> = if args.allowed_models.is_empty() {
-        Vec::new()
-    } else {
-        args.allowed_models
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect()
-    };
+    let allowed_models = parse_list(&args.allowed_models);
 
-    // Build config
-    let config = AiGatewayConfig {
+    Ok(AiGatewayConfig {
         prompt_injection_enabled: args.prompt_injection,
         pii_detection_enabled: args.pii_detection,
         pii_action,
@@ -124,34 +684,187 @@ async fn main() -> Result<()> {
             Some(args.max_tokens)
         },
         add_cost_headers: args.add_cost_headers,
+        cost_headers_for_unpriced: args.cost_headers_for_unpriced,
         allowed_models,
         block_mode: args.block_mode,
         fail_open: args.fail_open,
         rate_limit_requests: args.rate_limit_requests,
         rate_limit_tokens: args.rate_limit_tokens,
+        scan_roles: parse_list(&args.scan_roles),
+        trust_system_prompt: args.trust_system_prompt,
+        scan_image_metadata: args.scan_image_metadata,
+        model_match_case_insensitive: args.model_match_case_insensitive,
+        max_message_chars: if args.max_message_chars == 0 {
+            None
+        } else {
+            Some(args.max_message_chars)
+        },
+        rate_limit_window_seconds: args.rate_limit_window_seconds,
+        pii_scan_scope,
+        allow_test_cards: args.allow_test_cards,
+        scanned_methods: parse_list(&args.scanned_methods),
+        strict_mode: args.strict_mode,
+        pii_min_confidence: args.pii_min_confidence,
+        pii_min_matches: args.pii_min_matches,
+        detect_sampling_abuse: args.detect_sampling_abuse,
+        sampling_abuse_token_ids: parse_token_ids(&args.sampling_abuse_token_ids),
+        detect_repetition_stuffing: args.detect_repetition_stuffing,
+        repetition_max_run: args.repetition_max_run,
+        repetition_max_ratio,
+        multilingual_detection: args.multilingual_detection,
+        multilingual_languages: parse_list(&args.multilingual_languages),
+        audit_tag_prefix: args.audit_tag_prefix.clone(),
+        fail_open_on_error: args.fail_open_on_error,
+        fail_open_on_parse: args.fail_open_on_parse,
+        data_uri_detection_enabled: args.data_uri_detection,
+        block_data_uris: args.block_data_uris,
+        max_inline_base64_chars: args.max_inline_base64_chars,
+        max_response_tokens: if args.max_response_tokens == 0 {
+            None
+        } else {
+            Some(args.max_response_tokens)
+        },
+        ratelimit_reset_format,
+        pii_email_domain_allowlist: parse_list(&args.pii_email_domain_allowlist),
+        scan_timeout_ms: if args.scan_timeout_ms == 0 {
+            None
+        } else {
+            Some(args.scan_timeout_ms)
+        },
+        normalize_obfuscation: args.normalize_obfuscation,
+        pii_block_message_template: args.pii_block_message_template.clone(),
+        global_rate_limit_requests: args.global_rate_limit_requests,
+        global_rate_limit_tokens: args.global_rate_limit_tokens,
+        token_weight_text: args.token_weight_text,
+        token_weight_tool_json: args.token_weight_tool_json,
+        image_token_cost: args.image_token_cost,
+        check_order: parse_check_order(&args.check_order),
+        blocked_keywords: parse_list(&args.blocked_keywords),
+        blocked_keywords_sensitive: args.blocked_keywords_sensitive,
+        detect_nested_prompts: args.detect_nested_prompts,
+        rate_limit_bypass_ips: parse_list(&args.rate_limit_bypass_ips),
+        max_prompt_tokens: if args.max_prompt_tokens == 0 {
+            None
+        } else {
+            Some(args.max_prompt_tokens)
+        },
+        token_limit_target,
+        policy_version: args.policy_version.clone(),
+        external_moderation_url: args.external_moderation_url.clone(),
+        external_moderation_api_key: args.external_moderation_api_key.clone().map(Into::into),
+        external_moderation_timeout_ms: args.external_moderation_timeout_ms,
+        scan_sample_rate,
+        scan_sampled_checks: parse_sampled_checks(&args.scan_sampled_checks),
+        detect_injection_in_code: args.detect_injection_in_code,
+        injection_in_code_action,
+        scan_cache_size: args.scan_cache_size,
+        scan_cache_ttl_seconds: args.scan_cache_ttl_seconds,
+        required_headers: parse_list(&args.required_headers),
+        sensitive_headers: parse_list(&args.sensitive_headers),
+        strip_request_headers: parse_list(&args.strip_request_headers),
+        pii_deep_scan: args.pii_deep_scan,
+        max_temperature: args.max_temperature,
+        max_top_p: args.max_top_p,
+        rate_limit_burst: args.rate_limit_burst,
+        request_timeout_ms: if args.request_timeout_ms == 0 {
+            None
+        } else {
+            Some(args.request_timeout_ms)
+        },
+        exempt_correlation_prefixes: parse_list(&args.exempt_correlation_prefixes),
+        config_parse_failure_mode,
+        context_windows: parse_model_tokens(&args.context_windows, "context window"),
+        context_warn_fraction,
+        block_context_overflow: args.block_context_overflow,
+        scan_assistant_prefill: args.scan_assistant_prefill,
+        rate_limit_per_provider: args.rate_limit_per_provider,
+        min_tls_version,
+        incremental_scan: args.incremental_scan,
+        model_aliases: parse_model_aliases(&args.model_aliases),
+        pii_counts_header: args.pii_counts_header,
+        require_model: args.require_model,
+        default_model: args.default_model.clone(),
+        detect_hidden_unicode: args.detect_hidden_unicode,
+        per_model_token_limits: parse_model_tokens(
+            &args.per_model_token_limits,
+            "per-model token limit",
+        ),
+        injection_action,
+        jailbreak_action,
+        realtime_policy,
+        detection_db_path: args.detection_db_path.clone(),
+        ..Default::default()
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    // Initialize logging
+    let filter = if args.verbose {
+        EnvFilter::new("debug")
+    } else {
+        EnvFilter::new("info")
     };
 
+    fmt().with_env_filter(filter).with_target(false).init();
+
+    if let Some(ref path) = args.validate_config {
+        return validate_config_file(path);
+    }
+
+    let config = build_config(&args)?;
+
+    // Print the effective config and exit without starting the server
+    if args.dump_effective_config {
+        let dump = config.effective_config_json()?;
+        println!("{}", serde_json::to_string_pretty(&dump)?);
+        return Ok(());
+    }
+
     info!("Starting AI Gateway Agent");
-    info!("  Socket: {}", args.socket);
     info!(
         "  Prompt injection detection: {}",
         config.prompt_injection_enabled
     );
     info!("  PII detection: {}", config.pii_detection_enabled);
     info!("  PII action: {:?}", config.pii_action);
+    info!("  PII scan scope: {:?}", config.pii_scan_scope);
     info!(
         "  Jailbreak detection: {}",
         config.jailbreak_detection_enabled
     );
     info!("  Schema validation: {}", config.schema_validation_enabled);
     info!("  Max tokens: {:?}", config.max_tokens_per_request);
+    info!("  Max prompt tokens: {:?}", config.max_prompt_tokens);
+    info!("  Max message chars: {:?}", config.max_message_chars);
     info!("  Block mode: {}", config.block_mode);
-    info!("  Fail open: {}", config.fail_open);
+    info!(
+        "  Fail open: {} (errors: {}, parse: {})",
+        config.fail_open,
+        config.fails_open(FailureClass::Error),
+        config.fails_open(FailureClass::Parse)
+    );
+    info!("  Scan roles: {:?}", config.scan_roles);
 
     if config.rate_limit_requests > 0 || config.rate_limit_tokens > 0 {
         info!(
-            "  Rate limit: {} req/min, {} tokens/min",
-            config.rate_limit_requests, config.rate_limit_tokens
+            "  Rate limit: {} requests, {} tokens per {}s window",
+            config.rate_limit_requests, config.rate_limit_tokens, config.rate_limit_window_seconds
+        );
+    }
+
+    if config.rate_limit_burst > 0 {
+        info!("  Rate limit burst: {} requests", config.rate_limit_burst);
+    }
+
+    if config.global_rate_limit_requests > 0 || config.global_rate_limit_tokens > 0 {
+        info!(
+            "  Global rate limit: {} requests, {} tokens per {}s window",
+            config.global_rate_limit_requests,
+            config.global_rate_limit_tokens,
+            config.rate_limit_window_seconds
         );
     }
 
@@ -159,29 +872,37 @@ async fn main() -> Result<()> {
         info!("  Allowed models: {:?}", config.allowed_models);
     }
 
-    let agent = AiGatewayAgent::new(config);
+    let agent = SharedAiGatewayAgent::new(AiGatewayAgent::new(config));
 
-    // Choose transport based on CLI arguments
-    if let Some(grpc_addr) = args.grpc_address {
-        // Use gRPC transport (v2 protocol)
-        info!(
-            "Starting AI Gateway Agent with gRPC transport on {}",
-            grpc_addr
-        );
-        let addr: std::net::SocketAddr = grpc_addr
-            .parse()
-            .map_err(|e| anyhow::anyhow!("Invalid gRPC address '{}': {}", grpc_addr, e))?;
-        let server = GrpcAgentServerV2::new("ai-gateway", Box::new(agent));
-        server.run(addr).await?;
-    } else {
-        // Use UDS transport (v2 protocol)
-        info!(
-            "Starting AI Gateway Agent with UDS transport on {}",
-            args.socket
-        );
-        let server = UdsAgentServerV2::new("ai-gateway", &args.socket, Box::new(agent));
-        server.run().await?;
+    let (grpc_addr, socket) = select_transports(&args)?;
+
+    // Both transports share the same agent (config, rate limiter, request map)
+    let grpc = async {
+        if let Some(addr) = grpc_addr {
+            info!("Starting AI Gateway Agent with gRPC transport on {}", addr);
+            let server = GrpcAgentServerV2::new("ai-gateway", Box::new(agent.clone()));
+            server.run(addr).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+    let uds = async {
+        #[cfg(not(unix))]
+        let _ = socket;
+        #[cfg(unix)]
+        if let Some(socket) = socket {
+            info!("Starting AI Gateway Agent with UDS transport on {}", socket);
+            let server = UdsAgentServerV2::new("ai-gateway", &socket, Box::new(agent.clone()));
+            server.run().await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+    // The stats endpoint is a side channel: it must never take the agent down
+    if let Some(ref addr) = args.stats_address {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!("Serving detection stats on http://{}/stats", addr);
+        tokio::spawn(stats::serve(listener, agent.agent().stats()));
     }
+    tokio::try_join!(grpc, uds)?;
 
     Ok(())
 }
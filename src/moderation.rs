@@ -0,0 +1,169 @@
+//! External moderation API client.
+//!
+//! High-assurance deployments can send request content to an external
+//! classifier before allowing it. The request and response follow the
+//! OpenAI moderations API (`POST {"input": "..."}` returning
+//! `{"results": [{"flagged": true, "categories": {...}}]}`); internal
+//! classifiers may also answer with a bare `{"flagged": true}`.
+
+use crate::GatewayError;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Default time budget for one moderation call
+pub const DEFAULT_TIMEOUT_MS: u64 = 2000;
+
+/// Outcome of a moderation call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModerationVerdict {
+    /// Whether the endpoint flagged the content
+    pub flagged: bool,
+    /// Categories reported as flagged, sorted
+    pub categories: Vec<String>,
+}
+
+/// HTTP client for external moderation endpoints
+///
+/// One client is shared by every request so connections are pooled.
+#[derive(Debug, Clone, Default)]
+pub struct ModerationClient {
+    client: reqwest::Client,
+}
+
+impl ModerationClient {
+    /// Create a client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send content to a moderation endpoint
+    ///
+    /// Errors (connection failures, non-2xx status, unreadable responses,
+    /// and the timeout) are returned for the caller to handle per its
+    /// fail-open setting.
+    pub async fn moderate(
+        &self,
+        url: &str,
+        api_key: Option<&str>,
+        input: &str,
+        timeout: Duration,
+    ) -> Result<ModerationVerdict, GatewayError> {
+        let mut request = self
+            .client
+            .post(url)
+            .timeout(timeout)
+            .json(&serde_json::json!({ "input": input }));
+        if let Some(key) = api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| GatewayError::ModerationRequest(e.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(GatewayError::ModerationStatus(status.to_string()));
+        }
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| GatewayError::ModerationResponse(e.to_string()))?;
+        parse_verdict(&body)
+            .ok_or_else(|| GatewayError::ModerationResponse("no verdict".to_string()))
+    }
+}
+
+/// Read a verdict from an OpenAI-style or bare moderation response
+///
+/// With several results, the content is flagged when any result is.
+fn parse_verdict(body: &Value) -> Option<ModerationVerdict> {
+    let results: Vec<&Value> = match body.get("results").and_then(Value::as_array) {
+        Some(results) => results.iter().collect(),
+        None => vec![body],
+    };
+
+    let mut verdict = ModerationVerdict::default();
+    let mut found = false;
+    for result in results {
+        let Some(flagged) = result.get("flagged").and_then(Value::as_bool) else {
+            continue;
+        };
+        found = true;
+        verdict.flagged |= flagged;
+        match result.get("categories") {
+            Some(Value::Object(categories)) => verdict.categories.extend(
+                categories
+                    .iter()
+                    .filter(|(_, hit)| hit.as_bool() == Some(true))
+                    .map(|(name, _)| name.clone()),
+            ),
+            Some(Value::Array(categories)) => verdict.categories.extend(
+                categories
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from),
+            ),
+            _ => {}
+        }
+    }
+    verdict.categories.sort();
+    verdict.categories.dedup();
+    found.then_some(verdict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_openai_verdict() {
+        let body = serde_json::json!({
+            "id": "modr-1",
+            "results": [{
+                "flagged": true,
+                "categories": {"violence": true, "hate": false, "self-harm": true},
+                "category_scores": {"violence": 0.91}
+            }]
+        });
+        assert_eq!(
+            parse_verdict(&body),
+            Some(ModerationVerdict {
+                flagged: true,
+                categories: vec!["self-harm".to_string(), "violence".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_bare_verdict() {
+        let clean = serde_json::json!({"flagged": false});
+        assert_eq!(parse_verdict(&clean), Some(ModerationVerdict::default()));
+
+        let flagged = serde_json::json!({"flagged": true, "categories": ["secrets"]});
+        assert_eq!(
+            parse_verdict(&flagged).unwrap().categories,
+            vec!["secrets".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_verdict() {
+        assert_eq!(parse_verdict(&serde_json::json!({"ok": true})), None);
+        assert_eq!(parse_verdict(&serde_json::json!({"results": []})), None);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_endpoint_error() {
+        let err = ModerationClient::new()
+            .moderate(
+                "http://127.0.0.1:1/v1/moderations",
+                None,
+                "hello",
+                Duration::from_millis(500),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GatewayError::ModerationRequest(_)), "{}", err);
+    }
+}
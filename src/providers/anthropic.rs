@@ -1,7 +1,12 @@
 //! Anthropic API request parsing.
 
-use super::{AiProvider, AiRequest, Message};
+use super::{
+    join_parts, json_len, sampling_param, scannable_image_url, AiProvider, AiRequest, Message,
+};
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ops::Range;
 
 /// Anthropic messages API request format
 #[derive(Debug, Deserialize)]
@@ -10,10 +15,23 @@ struct AnthropicRequest {
     messages: Option<Vec<AnthropicMessage>>,
     max_tokens: Option<u32>,
     system: Option<AnthropicSystem>,
+    stop_sequences: Option<Vec<String>>,
+    stream: Option<Value>,
+    metadata: Option<AnthropicMetadata>,
+    /// Tool definitions
+    tools: Option<Value>,
+    temperature: Option<Value>,
+    top_p: Option<Value>,
     // Legacy completion API
     prompt: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct AnthropicMetadata {
+    /// End-user identifier
+    user_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct AnthropicMessage {
     role: String,
@@ -60,24 +78,64 @@ struct AnthropicContentBlock {
     #[serde(rename = "type")]
     content_type: String,
     text: Option<String>,
-    // image would be here for vision
+    source: Option<AnthropicImageSource>,
+    /// Arguments of a `tool_use` block
+    input: Option<Value>,
+}
+
+/// Image source: either a URL reference or inline base64 data
+#[derive(Debug, Deserialize)]
+struct AnthropicImageSource {
+    url: Option<String>,
+    media_type: Option<String>,
+}
+
+impl AnthropicImageSource {
+    /// Scannable text for this source (never the base64 payload)
+    fn as_text(&self) -> Option<String> {
+        if let Some(ref url) = self.url {
+            Some(scannable_image_url(url).to_string())
+        } else {
+            self.media_type.clone()
+        }
+    }
 }
 
 impl AnthropicContent {
-    fn as_text(&self) -> String {
+    /// Text of the content, with each text block's byte range for block content
+    fn text_parts(&self) -> (String, Vec<Range<usize>>) {
+        match self {
+            AnthropicContent::Text(s) => (s.clone(), Vec::new()),
+            AnthropicContent::Blocks(blocks) => join_parts(
+                blocks
+                    .iter()
+                    .filter(|b| b.content_type == "text")
+                    .filter_map(|b| b.text.as_deref()),
+            ),
+        }
+    }
+
+    /// Characters of `tool_use` input JSON in the content
+    fn tool_json_chars(&self) -> usize {
         match self {
-            AnthropicContent::Text(s) => s.clone(),
+            AnthropicContent::Text(_) => 0,
             AnthropicContent::Blocks(blocks) => blocks
                 .iter()
-                .filter_map(|b| {
-                    if b.content_type == "text" {
-                        b.text.clone()
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join(" "),
+                .filter_map(|b| b.input.as_ref())
+                .map(json_len)
+                .sum(),
+        }
+    }
+
+    /// Scannable text of any image sources in the content
+    fn image_sources(&self) -> Vec<String> {
+        match self {
+            AnthropicContent::Text(_) => Vec::new(),
+            AnthropicContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| b.source.as_ref())
+                .filter_map(|src| src.as_text())
+                .collect(),
         }
     }
 }
@@ -88,6 +146,8 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
 
     let mut messages = Vec::new();
     let mut system_prompt = None;
+    let mut image_metadata = Vec::new();
+    let mut tool_json_chars = parsed.tools.as_ref().map_or(0, json_len);
 
     // Extract system prompt
     if let Some(sys) = parsed.system {
@@ -97,10 +157,19 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
     // Handle messages API format
     if let Some(msgs) = parsed.messages {
         for msg in msgs {
-            let content = msg.content.as_text();
+            for source in msg.content.image_sources() {
+                image_metadata.push(Message {
+                    role: msg.role.clone(),
+                    content: source,
+                    parts: Vec::new(),
+                });
+            }
+            tool_json_chars += msg.content.tool_json_chars();
+            let (content, parts) = msg.content.text_parts();
             messages.push(Message {
                 role: msg.role,
                 content,
+                parts,
             });
         }
     }
@@ -117,6 +186,7 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
                     messages.push(Message {
                         role: "user".to_string(),
                         content: content.to_string(),
+                        parts: Vec::new(),
                     });
                 }
             } else if let Some(assistant_text) = part.strip_prefix("Assistant:") {
@@ -125,6 +195,7 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
                     messages.push(Message {
                         role: "assistant".to_string(),
                         content: content.to_string(),
+                        parts: Vec::new(),
                     });
                 }
             }
@@ -135,6 +206,7 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
             messages.push(Message {
                 role: "user".to_string(),
                 content: prompt,
+                parts: Vec::new(),
             });
         }
     }
@@ -148,7 +220,18 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
         model: parsed.model,
         messages,
         max_tokens: parsed.max_tokens,
+        completions: None,
         system_prompt,
+        image_metadata,
+        logit_bias: HashMap::new(),
+        stop: parsed.stop_sequences.unwrap_or_default(),
+        stream: parsed.stream.as_ref().and_then(Value::as_bool),
+        user_id: parsed.metadata.and_then(|m| m.user_id),
+        tool_json_chars,
+        temperature: sampling_param(parsed.temperature.as_ref()),
+        top_p: sampling_param(parsed.top_p.as_ref()),
+        prompts: None,
+        json_strings: Vec::new(),
     })
 }
 
@@ -156,6 +239,19 @@ pub fn parse_request(body: &str) -> Option<AiRequest> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_stream_and_stop_sequences() {
+        let body = r#"{
+            "model": "claude-3-opus-20240229",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "stream": false,
+            "stop_sequences": ["\n\nHuman:"]
+        }"#;
+        let req = parse_request(body).unwrap();
+        assert_eq!(req.stream, Some(false));
+        assert_eq!(req.stop, vec!["\n\nHuman:".to_string()]);
+    }
+
     #[test]
     fn test_parse_messages_api() {
         let body = r#"{
@@ -172,6 +268,21 @@ mod tests {
         assert_eq!(req.messages[0].role, "user");
         assert_eq!(req.messages[0].content, "Hello, Claude!");
         assert_eq!(req.max_tokens, Some(1024));
+        assert_eq!(req.user_id, None);
+    }
+
+    #[test]
+    fn test_parse_metadata_user_id() {
+        let body = r#"{
+            "model": "claude-3-opus-20240229",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "metadata": {"user_id": "user-1234"}
+        }"#;
+
+        let req = parse_request(body).unwrap();
+        assert_eq!(req.user_id.as_deref(), Some("user-1234"));
+        // The identifier is not message content
+        assert_eq!(req.all_content(), vec!["Hello"]);
     }
 
     #[test]
@@ -231,6 +342,29 @@ mod tests {
 
         let req = parse_request(body).unwrap();
         assert_eq!(req.messages[0].content, "What's in this image?");
+        // Base64 payload is never surfaced as scannable text
+        assert!(req.image_metadata.iter().all(|m| m.content != "..."));
+    }
+
+    #[test]
+    fn test_parse_image_url_source() {
+        let body = r#"{
+            "model": "claude-3-5-sonnet-20241022",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": [
+                        {"type": "image", "source": {"type": "url", "url": "https://example.com/cat.png"}},
+                        {"type": "text", "text": "What animal is this?"}
+                    ]
+                }
+            ],
+            "max_tokens": 1024
+        }"#;
+
+        let req = parse_request(body).unwrap();
+        assert_eq!(req.image_metadata.len(), 1);
+        assert_eq!(req.image_metadata[0].content, "https://example.com/cat.png");
     }
 
     #[test]
@@ -2,10 +2,15 @@
 
 pub mod anthropic;
 pub mod openai;
+pub mod response;
+pub mod responses;
 pub mod schema;
+pub(crate) mod stream;
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
 
 /// Detected AI provider
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
@@ -28,6 +33,54 @@ impl AiProvider {
             AiProvider::Unknown => "unknown",
         }
     }
+
+    /// Heuristic tokenizer profile for this provider's request format
+    pub fn token_profile(&self) -> TokenProfile {
+        match self {
+            // ChatML frames each message with ~3 tokens and primes the reply
+            // with 3 more (OpenAI cookbook token counting)
+            AiProvider::OpenAI | AiProvider::Azure => TokenProfile {
+                chars_per_token: 4.0,
+                per_message: 3,
+                per_request: 3,
+                tool_use: 0,
+            },
+            // Claude's tokenizer is denser on English text, and tool use adds
+            // a ~346 token system prompt once tools are defined
+            AiProvider::Anthropic => TokenProfile {
+                chars_per_token: 3.5,
+                per_message: 3,
+                per_request: 0,
+                tool_use: 346,
+            },
+            AiProvider::Unknown => TokenProfile::default(),
+        }
+    }
+}
+
+/// Per-provider constants for heuristic token estimation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenProfile {
+    /// Average characters per token for prose
+    pub chars_per_token: f32,
+    /// Framing tokens added to every message
+    pub per_message: u32,
+    /// Fixed tokens added once per request
+    pub per_request: u32,
+    /// Fixed tokens added when the request carries tool JSON
+    pub tool_use: u32,
+}
+
+impl Default for TokenProfile {
+    /// Plain ~4 characters per token with no framing overhead
+    fn default() -> Self {
+        Self {
+            chars_per_token: 4.0,
+            per_message: 0,
+            per_request: 0,
+            tool_use: 0,
+        }
+    }
 }
 
 /// A message in a conversation
@@ -35,30 +88,214 @@ impl AiProvider {
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// Byte ranges of each content part within `content`, when the message
+    /// was sent as an array of parts; empty for plain string content
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<Range<usize>>,
+}
+
+impl Message {
+    /// Index of the content part holding a byte offset into `content`
+    ///
+    /// `None` for plain string content and for the separators between parts.
+    pub fn part_at(&self, offset: usize) -> Option<usize> {
+        self.parts.iter().position(|part| part.contains(&offset))
+    }
+
+    /// Text of each content part, in order; empty for plain string content
+    pub fn part_texts(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().map(|part| &self.content[part.clone()])
+    }
+}
+
+/// A piece of request text tagged with the role that produced it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaggedContent<'a> {
+    pub role: &'a str,
+    pub text: &'a str,
 }
 
 /// Parsed AI request
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AiRequest {
     pub provider: AiProvider,
     pub model: Option<String>,
     pub messages: Vec<Message>,
     pub max_tokens: Option<u32>,
+    /// Number of completions generated (`n`, or `best_of` when larger)
+    pub completions: Option<u32>,
+    /// Prompts submitted in one request (array-form `prompt`), each its own message
+    pub prompts: Option<u32>,
     pub system_prompt: Option<String>,
+    /// Text from image reference fields (URLs), tagged with the owning message role
+    ///
+    /// Base64 image payloads are never included.
+    pub image_metadata: Vec<Message>,
+    /// Token ID biases requested via `logit_bias` (non-numeric keys are dropped)
+    pub logit_bias: HashMap<u32, f32>,
+    /// Stop sequences (`stop` / `stop_sequences`)
+    pub stop: Vec<String>,
+    /// Whether the client asked for a server-sent event stream (`stream`)
+    pub stream: Option<bool>,
+    /// End-user identifier (`user` / `metadata.user_id`)
+    ///
+    /// Scanned for PII only; it is an identifier, not prompt text.
+    pub user_id: Option<String>,
+    /// Characters of tool JSON: definitions (`tools`) and tool calls
+    pub tool_json_chars: usize,
+    /// Sampling temperature
+    pub temperature: Option<f32>,
+    /// Nucleus sampling probability mass
+    pub top_p: Option<f32>,
+    /// Every string value in the raw body, for deep PII scans
+    ///
+    /// Not filled by the parsers; see [`json_strings`].
+    pub json_strings: Vec<String>,
+}
+
+/// Default tokens charged per image reference
+///
+/// A 1024x1024 image at high detail costs 765 tokens on OpenAI and roughly
+/// the same on Anthropic (pixels / 750, capped near 1600).
+pub const DEFAULT_IMAGE_TOKENS: u32 = 765;
+
+/// Per-content-type weights for token estimation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenWeights {
+    /// Multiplier on the provider's characters per token estimate for message text
+    pub text: f32,
+    /// Multiplier on the provider's characters per token estimate for tool JSON
+    pub tool_json: f32,
+    /// Fixed tokens per image reference
+    pub image: u32,
+}
+
+impl Default for TokenWeights {
+    fn default() -> Self {
+        Self {
+            text: 1.0,
+            // Quotes, braces and keys tokenize at ~3 characters per token
+            tool_json: 1.3,
+            image: DEFAULT_IMAGE_TOKENS,
+        }
+    }
 }
 
 impl AiRequest {
     /// Get all text content from the request for scanning
     pub fn all_content(&self) -> Vec<&str> {
-        let mut content: Vec<&str> = self.messages.iter().map(|m| m.content.as_str()).collect();
+        self.tagged_content().into_iter().map(|c| c.text).collect()
+    }
+
+    /// Get all text content from the request, tagged with its role
+    ///
+    /// The system prompt is reported with the `system` role.
+    pub fn tagged_content(&self) -> Vec<TaggedContent<'_>> {
+        let mut content: Vec<TaggedContent<'_>> = self
+            .messages
+            .iter()
+            .map(|m| TaggedContent {
+                role: m.role.as_str(),
+                text: m.content.as_str(),
+            })
+            .collect();
         if let Some(ref sys) = self.system_prompt {
-            content.push(sys.as_str());
+            content.push(TaggedContent {
+                role: "system",
+                text: sys.as_str(),
+            });
         }
         content
     }
 
-    /// Estimate token count (rough approximation)
+    /// Text of the system prompt proper
+    ///
+    /// The `system` messages that open the conversation, and a top-level
+    /// system prompt (Anthropic `system`, Responses `instructions`). A
+    /// `system` message after the first user or assistant turn is not part
+    /// of it.
+    pub fn leading_system_content(&self) -> Vec<&str> {
+        self.messages
+            .iter()
+            .take_while(|m| m.role == "system")
+            .map(|m| m.content.as_str())
+            .chain(self.system_prompt.as_deref())
+            .collect()
+    }
+
+    /// Text of a trailing `assistant` message, which prefills the reply
+    ///
+    /// Anthropic continues its answer from a final assistant turn; other
+    /// providers may accept one too.
+    pub fn assistant_prefill(&self) -> Option<&str> {
+        self.messages
+            .last()
+            .filter(|m| m.role == "assistant" && !m.content.trim().is_empty())
+            .map(|m| m.content.as_str())
+    }
+
+    /// Get text content from the given roles only (empty = all roles)
+    pub fn content_for_roles(&self, roles: &[String]) -> Vec<&str> {
+        self.tagged_content()
+            .into_iter()
+            .filter(|c| role_matches(roles, c.role))
+            .map(|c| c.text)
+            .collect()
+    }
+
+    /// Get image metadata text from the given roles only (empty = all roles)
+    pub fn image_metadata_for_roles(&self, roles: &[String]) -> Vec<&str> {
+        self.image_metadata
+            .iter()
+            .filter(|m| role_matches(roles, &m.role))
+            .map(|m| m.content.as_str())
+            .collect()
+    }
+
+    /// Number of completions the provider will generate (at least 1)
+    pub fn completion_count(&self) -> u32 {
+        self.completions.unwrap_or(1).max(1)
+    }
+
+    /// Number of prompts the provider will complete (at least 1)
+    pub fn prompt_count(&self) -> u32 {
+        self.prompts.unwrap_or(1).max(1)
+    }
+
+    /// Estimate output tokens: `max_tokens` for each completion of each prompt
+    pub fn estimate_output_tokens(&self) -> u32 {
+        self.max_tokens
+            .unwrap_or(0)
+            .saturating_mul(self.completion_count())
+            .saturating_mul(self.prompt_count())
+    }
+
+    /// Output tokens of the completions beyond the first: `max_tokens` for
+    /// each extra `n`/`best_of` completion and batch prompt
+    ///
+    /// Added on top of the prompt estimate for cost and token rate limiting,
+    /// so a request asking for a single completion is charged as before.
+    pub fn estimate_extra_output_tokens(&self) -> u32 {
+        let completions = self.completion_count().saturating_mul(self.prompt_count());
+        self.max_tokens.unwrap_or(0).saturating_mul(completions - 1)
+    }
+
+    /// Number of images referenced (URLs and inline base64)
+    pub fn image_count(&self) -> usize {
+        self.image_metadata.len()
+    }
+
+    /// Estimate token count with the default [`TokenWeights`]
     pub fn estimate_tokens(&self) -> u32 {
+        self.estimate_tokens_with(&TokenWeights::default())
+    }
+
+    /// Estimate token count (rough approximation), weighted by content type
+    ///
+    /// Characters per token and framing overhead follow the provider's
+    /// [`TokenProfile`].
+    pub fn estimate_tokens_with(&self, weights: &TokenWeights) -> u32 {
+        let profile = self.provider.token_profile();
         let total_chars: usize = self
             .messages
             .iter()
@@ -67,48 +304,236 @@ impl AiRequest {
 
         let system_chars = self.system_prompt.as_ref().map(|s| s.len()).unwrap_or(0);
 
-        // Rough estimate: ~4 characters per token for English
-        ((total_chars + system_chars) as f32 / 4.0).ceil() as u32
+        let chars_per_token = profile.chars_per_token;
+        let text = (total_chars + system_chars) as f32 / chars_per_token * weights.text;
+        let tool_json = self.tool_json_chars as f32 / chars_per_token * weights.tool_json;
+        let images = self.image_count() as f32 * weights.image as f32;
+
+        let mut overhead =
+            profile.per_request as usize + profile.per_message as usize * self.messages.len();
+        if self.tool_json_chars > 0 {
+            overhead += profile.tool_use as usize;
+        }
+
+        (text + tool_json + images).ceil() as u32 + overhead as u32
+    }
+}
+
+/// Length of a JSON value in its compact serialized form
+pub(crate) fn json_len(value: &serde_json::Value) -> usize {
+    serde_json::to_string(value).map_or(0, |s| s.len())
+}
+
+/// Join content part texts with a space, recording each part's byte range
+pub(crate) fn join_parts<'a>(
+    texts: impl IntoIterator<Item = &'a str>,
+) -> (String, Vec<Range<usize>>) {
+    let mut joined = String::new();
+    let mut parts = Vec::new();
+    for text in texts {
+        if !parts.is_empty() {
+            joined.push(' ');
+        }
+        let start = joined.len();
+        joined.push_str(text);
+        parts.push(start..joined.len());
+    }
+    (joined, parts)
+}
+
+/// Check whether a role is selected by a role list (empty = all roles)
+fn role_matches(roles: &[String], role: &str) -> bool {
+    roles.is_empty() || roles.iter().any(|r| r.eq_ignore_ascii_case(role))
+}
+
+/// Most rounds of percent-decoding applied to an image URL
+const MAX_DECODE_PASSES: usize = 3;
+
+/// Get the scannable text of an image URL
+///
+/// For base64 `data:` URIs only the media-type prefix is kept; the encoded
+/// image payload is not text and would only produce noise for the detectors.
+/// The rest is percent-decoded, so `%69gnore%20previous` reads as `ignore
+/// previous`.
+pub(crate) fn scannable_image_url(url: &str) -> Cow<'_, str> {
+    let url = match url.find(";base64,") {
+        Some(idx) if url.starts_with("data:") => &url[..idx],
+        _ => url,
+    };
+    percent_decode(url)
+}
+
+/// Decode `%XX` escapes and `+` as a space, leaving invalid escapes as they are
+///
+/// Decoding repeats until the text stops changing, up to
+/// [`MAX_DECODE_PASSES`] rounds, to undo double encoding.
+fn percent_decode(text: &str) -> Cow<'_, str> {
+    let hex = |b: Option<&u8>| b.and_then(|&b| (b as char).to_digit(16));
+    let mut decoded = Cow::Borrowed(text);
+    for _ in 0..MAX_DECODE_PASSES {
+        if !decoded.contains(['%', '+']) {
+            break;
+        }
+        let bytes = decoded.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' => match (hex(bytes.get(i + 1)), hex(bytes.get(i + 2))) {
+                    (Some(high), Some(low)) => {
+                        out.push((high * 16 + low) as u8);
+                        i += 3;
+                        continue;
+                    }
+                    _ => out.push(b'%'),
+                },
+                b'+' => out.push(b' '),
+                b => out.push(b),
+            }
+            i += 1;
+        }
+        let next = String::from_utf8_lossy(&out).into_owned();
+        if next == decoded {
+            break;
+        }
+        decoded = Cow::Owned(next);
+    }
+    decoded
+}
+
+/// Provider indicated by the request's auth headers, if any
+///
+/// - `api-key` is only used by Azure OpenAI
+/// - `x-api-key`, `anthropic-version` or a `Bearer sk-ant-` token is Anthropic
+/// - any other `Bearer sk-` token is OpenAI
+fn auth_provider(headers: &HashMap<String, Vec<String>>) -> Option<AiProvider> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, values)| values)
+    };
+
+    if header("api-key").is_some() {
+        return Some(AiProvider::Azure);
+    }
+    if header("x-api-key").is_some() || header("anthropic-version").is_some() {
+        return Some(AiProvider::Anthropic);
+    }
+    let bearer = header("authorization")
+        .into_iter()
+        .flatten()
+        .find_map(|v| v.strip_prefix("Bearer "));
+    match bearer {
+        Some(token) if token.starts_with("sk-ant-") => Some(AiProvider::Anthropic),
+        Some(token) if token.starts_with("sk-") => Some(AiProvider::OpenAI),
+        _ => None,
     }
 }
 
 /// Detect provider from request path and headers
+///
+/// Paths decide when they are unambiguous (Azure deployments, Anthropic
+/// messages). OpenAI-style paths are also served by Azure, so an Azure
+/// `api-key` header or an `api-version` query on them, or on an `/openai/...`
+/// path, selects Azure.
 pub fn detect_provider(path: &str, headers: &HashMap<String, Vec<String>>) -> AiProvider {
+    let (route, query) = path.split_once('?').unwrap_or((path, ""));
+    let auth = auth_provider(headers);
+
     // Check path patterns
-    if path.contains("/openai/deployments/") {
+    if route.contains("/openai/deployments/") {
         return AiProvider::Azure;
     }
 
-    if path.starts_with("/v1/chat/completions")
-        || path.starts_with("/v1/completions")
-        || path.starts_with("/v1/embeddings")
+    // Anthropic routes are never Azure's, whatever the query says
+    if route.starts_with("/v1/messages")
+        || route == "/v1/complete"
+        || route.starts_with("/v1/complete/")
     {
-        // Could be OpenAI or Anthropic - check headers
-        if headers.contains_key("anthropic-version") || headers.contains_key("x-api-key") {
-            // Check if it looks like Anthropic path
-            if path.starts_with("/v1/messages") || path.starts_with("/v1/complete") {
-                return AiProvider::Anthropic;
-            }
-        }
+        return AiProvider::Anthropic;
+    }
 
-        // Check for OpenAI-style auth
-        if let Some(auth) = headers.get("authorization") {
-            if auth.iter().any(|v| v.starts_with("Bearer sk-")) {
-                return AiProvider::OpenAI;
-            }
-        }
+    let openai_route = route.starts_with("/v1/chat/completions")
+        || route.starts_with("/v1/completions")
+        || route.starts_with("/v1/responses")
+        || route.starts_with("/v1/embeddings");
+    let azure_hint =
+        auth == Some(AiProvider::Azure) || query.split('&').any(|p| p.starts_with("api-version="));
 
-        // Default to OpenAI for these paths
-        return AiProvider::OpenAI;
+    // Azure OpenAI v1 paths (/openai/v1/chat/completions) without a deployment
+    if route.starts_with("/openai/") && azure_hint {
+        return AiProvider::Azure;
     }
 
-    if path.starts_with("/v1/messages") || path.starts_with("/v1/complete") {
-        return AiProvider::Anthropic;
+    if openai_route {
+        // OpenAI format; Azure when it carries Azure's auth header or query.
+        // Anthropic's OpenAI-compatible endpoint also takes this format, so
+        // it stays OpenAI.
+        if azure_hint {
+            return AiProvider::Azure;
+        }
+        return AiProvider::OpenAI;
     }
 
     AiProvider::Unknown
 }
 
+/// Whether the request opens an OpenAI Realtime API session
+///
+/// Realtime sessions are a WebSocket upgrade on `/v1/realtime` (or Azure's
+/// `/openai/realtime`). Their event protocol is not parsed, so the body
+/// detectors never see them.
+pub fn is_realtime_upgrade(path: &str, headers: &HashMap<String, Vec<String>>) -> bool {
+    let route = path.split_once('?').map_or(path, |(route, _)| route);
+    if !(route.starts_with("/v1/realtime") || route.starts_with("/openai/realtime")) {
+        return false;
+    }
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("upgrade"))
+        .flat_map(|(_, values)| values)
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case("websocket"))
+}
+
+/// Read a numeric sampling parameter, ignoring values of the wrong type
+///
+/// A mistyped value is left to schema validation rather than failing the
+/// whole parse, which would let the request through unrecognized.
+fn sampling_param(value: Option<&serde_json::Value>) -> Option<f32> {
+    value.and_then(serde_json::Value::as_f64).map(|v| v as f32)
+}
+
+/// Every string value in a JSON body
+///
+/// Object keys are not included. Returns nothing when the body is not JSON.
+pub fn json_strings(body: &str) -> Vec<String> {
+    fn collect(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::String(s) => out.push(s.clone()),
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, out)),
+            serde_json::Value::Object(map) => map.values().for_each(|v| collect(v, out)),
+            _ => {}
+        }
+    }
+
+    let mut out = Vec::new();
+    if let Ok(value) = serde_json::from_str(body) {
+        collect(&value, &mut out);
+    }
+    out
+}
+
+/// Whether a body opens as JSON but does not parse
+///
+/// Bodies that do not start with `{` or `[`, such as multipart uploads, are
+/// some other format rather than malformed JSON.
+pub fn is_malformed_json(body: &str) -> bool {
+    body.trim_start().starts_with(['{', '['])
+        && serde_json::from_str::<serde::de::IgnoredAny>(body).is_err()
+}
+
 /// Parse request body based on detected provider
 pub fn parse_request(provider: AiProvider, body: &str) -> Option<AiRequest> {
     match provider {
@@ -125,6 +550,17 @@ pub fn parse_request(provider: AiProvider, body: &str) -> Option<AiRequest> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_malformed_json() {
+        assert!(is_malformed_json(r#"{"model":"gpt-4","messages":["#));
+        assert!(is_malformed_json(r#"  {"model":"gpt-4"} trailing"#));
+        assert!(!is_malformed_json(r#"{"model":"gpt-4"}"#));
+        assert!(!is_malformed_json(""));
+        assert!(!is_malformed_json(
+            "--boundary\r\nContent-Disposition: form-data"
+        ));
+    }
+
     #[test]
     fn test_detect_openai() {
         let headers = HashMap::new();
@@ -134,6 +570,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_detect_openai_responses() {
+        let headers = HashMap::new();
+        assert_eq!(
+            detect_provider("/v1/responses", &headers),
+            AiProvider::OpenAI
+        );
+    }
+
+    #[test]
+    fn test_detect_realtime_upgrade() {
+        let path = "/v1/realtime?model=gpt-4o-realtime";
+        let mut headers = HashMap::new();
+        assert!(!is_realtime_upgrade(path, &headers));
+
+        headers.insert("Upgrade".to_string(), vec!["WebSocket".to_string()]);
+        assert!(is_realtime_upgrade(path, &headers));
+        assert!(is_realtime_upgrade("/openai/realtime", &headers));
+        assert!(!is_realtime_upgrade("/v1/chat/completions", &headers));
+    }
+
     #[test]
     fn test_detect_anthropic() {
         let headers = HashMap::new();
@@ -143,6 +600,116 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_content_for_roles() {
+        let request = AiRequest {
+            provider: AiProvider::OpenAI,
+            messages: vec![
+                Message {
+                    role: "user".to_string(),
+                    content: "question".to_string(),
+                    parts: Vec::new(),
+                },
+                Message {
+                    role: "assistant".to_string(),
+                    content: "answer".to_string(),
+                    parts: Vec::new(),
+                },
+            ],
+            system_prompt: Some("be helpful".to_string()),
+            ..Default::default()
+        };
+
+        let roles = vec!["user".to_string(), "system".to_string()];
+        assert_eq!(
+            request.content_for_roles(&roles),
+            vec!["question", "be helpful"]
+        );
+        assert_eq!(request.content_for_roles(&[]).len(), 3);
+    }
+
+    #[test]
+    fn test_json_strings() {
+        let body = r#"{"a":"x","b":[1,"y",{"c":"z"}],"d":null}"#;
+        assert_eq!(json_strings(body), vec!["x", "y", "z"]);
+        assert!(json_strings("not json").is_empty());
+    }
+
+    fn prose_request(provider: AiProvider, tool_json_chars: usize) -> AiRequest {
+        AiRequest {
+            provider,
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: "Summarize the quarterly report in three short bullet points.".repeat(20),
+                parts: Vec::new(),
+            }],
+            system_prompt: Some("You are a concise analyst.".to_string()),
+            tool_json_chars,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_token_estimates_per_provider() {
+        let openai = prose_request(AiProvider::OpenAI, 0).estimate_tokens();
+        let azure = prose_request(AiProvider::Azure, 0).estimate_tokens();
+        let anthropic = prose_request(AiProvider::Anthropic, 0).estimate_tokens();
+        let unknown = prose_request(AiProvider::Unknown, 0).estimate_tokens();
+
+        // 1230 characters of text
+        assert_eq!(unknown, 308);
+        assert_eq!(openai, 308 + 3 + 3);
+        assert_eq!(azure, openai);
+        // Denser tokenizer: 1230 / 3.5 rounds up to 352, plus message framing
+        assert_eq!(anthropic, 352 + 3);
+        assert!(anthropic > openai);
+    }
+
+    #[test]
+    fn test_anthropic_tool_use_overhead() {
+        let without = prose_request(AiProvider::Anthropic, 0).estimate_tokens();
+        let with = prose_request(AiProvider::Anthropic, 70).estimate_tokens();
+        // 70 characters of tool JSON at 1.3x, plus the tool use system prompt
+        assert_eq!(with - without, 26 + 346);
+
+        let openai_without = prose_request(AiProvider::OpenAI, 0).estimate_tokens();
+        let openai_with = prose_request(AiProvider::OpenAI, 70).estimate_tokens();
+        assert_eq!(openai_with - openai_without, 23);
+    }
+
+    #[test]
+    fn test_scannable_image_url() {
+        assert_eq!(
+            scannable_image_url("https://example.com/cat.png"),
+            "https://example.com/cat.png"
+        );
+        assert_eq!(
+            scannable_image_url("data:image/png;base64,iVBORw0KGgo="),
+            "data:image/png"
+        );
+        assert_eq!(
+            scannable_image_url("data:text/plain,hello"),
+            "data:text/plain,hello"
+        );
+    }
+
+    #[test]
+    fn test_scannable_image_url_is_percent_decoded() {
+        assert_eq!(
+            scannable_image_url("https://example.com/a.png?q=%69gnore%20previous+instructions"),
+            "https://example.com/a.png?q=ignore previous instructions"
+        );
+        // Double encoding is undone too
+        assert_eq!(
+            scannable_image_url("https://example.com/%2569gnore"),
+            "https://example.com/ignore"
+        );
+        assert_eq!(
+            scannable_image_url("data:text/plain,caf%C3%A9%20100%"),
+            "data:text/plain,café 100%"
+        );
+    }
+
     #[test]
     fn test_detect_azure() {
         let headers = HashMap::new();
@@ -151,4 +718,87 @@ mod tests {
             AiProvider::Azure
         );
     }
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_azure_api_key_only() {
+        let azure = headers(&[("Api-Key", "0123456789abcdef")]);
+        assert_eq!(
+            detect_provider("/openai/v1/chat/completions", &azure),
+            AiProvider::Azure
+        );
+        assert_eq!(
+            detect_provider("/v1/chat/completions", &azure),
+            AiProvider::Azure
+        );
+        assert_eq!(
+            detect_provider("/openai/v1/responses?api-version=preview", &HashMap::new()),
+            AiProvider::Azure
+        );
+
+        assert_eq!(
+            detect_provider(
+                "/v1/chat/completions?api-version=2024-06-01",
+                &HashMap::new()
+            ),
+            AiProvider::Azure
+        );
+
+        // Without Azure auth, a gateway-style /openai/ prefix stays unclassified
+        let openai = headers(&[("authorization", "Bearer sk-proj-abc")]);
+        assert_eq!(
+            detect_provider("/openai/v1/chat/completions", &openai),
+            AiProvider::Unknown
+        );
+    }
+
+    #[test]
+    fn test_detect_by_auth_headers() {
+        let openai = headers(&[("authorization", "Bearer sk-proj-abc")]);
+        assert_eq!(
+            detect_provider("/v1/chat/completions", &openai),
+            AiProvider::OpenAI
+        );
+        // Anthropic's OpenAI-compatible endpoint keeps the OpenAI format
+        let anthropic = headers(&[("x-api-key", "sk-ant-abc")]);
+        assert_eq!(
+            detect_provider("/v1/chat/completions", &anthropic),
+            AiProvider::OpenAI
+        );
+        assert_eq!(auth_provider(&anthropic), Some(AiProvider::Anthropic));
+        let anthropic_bearer = headers(&[("Authorization", "Bearer sk-ant-oat01-abc")]);
+        assert_eq!(
+            auth_provider(&anthropic_bearer),
+            Some(AiProvider::Anthropic)
+        );
+        assert_eq!(auth_provider(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_api_version_does_not_override_anthropic_routes() {
+        let none = HashMap::new();
+        assert_eq!(
+            detect_provider("/v1/messages?api-version=2023-06-01", &none),
+            AiProvider::Anthropic
+        );
+        assert_eq!(
+            detect_provider("/v1/complete?api-version=1", &none),
+            AiProvider::Anthropic
+        );
+        assert_eq!(
+            detect_provider("/v1/completions", &none),
+            AiProvider::OpenAI
+        );
+        // Nor classify unrelated paths as Azure
+        assert_eq!(
+            detect_provider("/health?api-version=1", &none),
+            AiProvider::Unknown
+        );
+    }
 }
@@ -0,0 +1,184 @@
+//! Provider response inspection.
+
+use super::AiProvider;
+use serde_json::Value;
+
+/// Estimate tokens generated in a (non-streaming) provider response
+///
+/// Uses the provider-reported usage (`completion_tokens` / `output_tokens`)
+/// when present, otherwise ~4 characters per token over the generated text.
+/// Bodies that are not JSON are estimated as a whole.
+pub fn estimate_generated_tokens(provider: AiProvider, body: &str) -> u32 {
+    let Ok(value) = serde_json::from_str::<Value>(body) else {
+        return chars_to_tokens(body.len());
+    };
+
+    let usage_key = match provider {
+        AiProvider::Anthropic => "output_tokens",
+        _ => "completion_tokens",
+    };
+    if let Some(tokens) = value
+        .get("usage")
+        .and_then(|u| u.get(usage_key))
+        .and_then(Value::as_u64)
+    {
+        return u32::try_from(tokens).unwrap_or(u32::MAX);
+    }
+
+    chars_to_tokens(generated_text_len(&value))
+}
+
+/// Estimate tokens generated in a streamed (server-sent events) response
+///
+/// Uses the last usage reported by an event (OpenAI's final chunk with
+/// `stream_options.include_usage`, Anthropic's `message_delta`) when present,
+/// otherwise ~4 characters per token over the text deltas. Events that are
+/// not JSON, such as `data: [DONE]`, are skipped.
+pub fn estimate_streamed_tokens(provider: AiProvider, body: &str) -> u32 {
+    let usage_key = match provider {
+        AiProvider::Anthropic => "output_tokens",
+        _ => "completion_tokens",
+    };
+    let mut usage = None;
+    let mut text_len = 0;
+    for event in sse_events(body) {
+        if let Some(tokens) = event
+            .get("usage")
+            .and_then(|u| u.get(usage_key))
+            .and_then(Value::as_u64)
+        {
+            usage = Some(tokens);
+        }
+        text_len += delta_text_len(&event);
+    }
+    match usage {
+        Some(tokens) => u32::try_from(tokens).unwrap_or(u32::MAX),
+        None => chars_to_tokens(text_len),
+    }
+}
+
+/// JSON payloads of the `data:` lines in an event stream
+fn sse_events(body: &str) -> impl Iterator<Item = Value> + '_ {
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str(data.trim()).ok())
+}
+
+/// Length of the generated text in one OpenAI or Anthropic stream event
+fn delta_text_len(event: &Value) -> usize {
+    let text_len = |v: Option<&Value>| v.and_then(Value::as_str).map_or(0, str::len);
+
+    // OpenAI: choices[].delta.content or choices[].text (legacy)
+    let openai: usize = event
+        .get("choices")
+        .and_then(Value::as_array)
+        .map(|choices| {
+            choices
+                .iter()
+                .map(|c| {
+                    text_len(c.get("delta").and_then(|d| d.get("content")))
+                        + text_len(c.get("text"))
+                })
+                .sum()
+        })
+        .unwrap_or(0);
+
+    // Anthropic: content_block_delta with delta.text
+    let anthropic = text_len(event.get("delta").and_then(|d| d.get("text")));
+
+    openai + anthropic
+}
+
+/// Length of the generated text in an OpenAI or Anthropic response
+fn generated_text_len(value: &Value) -> usize {
+    let text_len = |v: Option<&Value>| v.and_then(Value::as_str).map_or(0, str::len);
+
+    // OpenAI: choices[].message.content or choices[].text (legacy)
+    let openai: usize = value
+        .get("choices")
+        .and_then(Value::as_array)
+        .map(|choices| {
+            choices
+                .iter()
+                .map(|c| {
+                    text_len(c.get("message").and_then(|m| m.get("content")))
+                        + text_len(c.get("text"))
+                })
+                .sum()
+        })
+        .unwrap_or(0);
+
+    // Anthropic: content[].text
+    let anthropic: usize = value
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|blocks| blocks.iter().map(|b| text_len(b.get("text"))).sum())
+        .unwrap_or(0);
+
+    openai + anthropic
+}
+
+fn chars_to_tokens(chars: usize) -> u32 {
+    u32::try_from(chars.div_ceil(4)).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefers_reported_usage() {
+        let body =
+            r#"{"choices": [{"message": {"content": "Hi"}}], "usage": {"completion_tokens": 42}}"#;
+        assert_eq!(estimate_generated_tokens(AiProvider::OpenAI, body), 42);
+
+        let body =
+            r#"{"content": [{"type": "text", "text": "Hi"}], "usage": {"output_tokens": 7}}"#;
+        assert_eq!(estimate_generated_tokens(AiProvider::Anthropic, body), 7);
+    }
+
+    #[test]
+    fn test_estimates_from_text() {
+        let text = "a".repeat(400);
+        let body = format!(
+            r#"{{"choices": [{{"message": {{"content": "{}"}}}}]}}"#,
+            text
+        );
+        assert_eq!(estimate_generated_tokens(AiProvider::OpenAI, &body), 100);
+
+        let body = format!(r#"{{"content": [{{"type": "text", "text": "{}"}}]}}"#, text);
+        assert_eq!(estimate_generated_tokens(AiProvider::Anthropic, &body), 100);
+
+        assert_eq!(estimate_generated_tokens(AiProvider::OpenAI, "not json"), 2);
+    }
+
+    #[test]
+    fn test_streamed_openai_response() {
+        let body = concat!(
+            "data: {\"choices\": [{\"delta\": {\"role\": \"assistant\"}}]}\n\n",
+            "data: {\"choices\": [{\"delta\": {\"content\": \"Hello \"}}]}\n\n",
+            "data: {\"choices\": [{\"delta\": {\"content\": \"there!\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        assert_eq!(estimate_streamed_tokens(AiProvider::OpenAI, body), 3);
+
+        let with_usage = format!(
+            "{}data: {{\"choices\": [], \"usage\": {{\"completion_tokens\": 9}}}}\n\n",
+            body
+        );
+        assert_eq!(estimate_streamed_tokens(AiProvider::OpenAI, &with_usage), 9);
+    }
+
+    #[test]
+    fn test_streamed_anthropic_response() {
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\": \"message_start\", \"message\": {\"usage\": {\"output_tokens\": 1}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\": \"content_block_delta\", \"delta\": {\"type\": \"text_delta\", \"text\": \"Hi\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\": \"message_delta\", \"usage\": {\"output_tokens\": 15}}\n\n",
+        );
+        assert_eq!(estimate_streamed_tokens(AiProvider::Anthropic, body), 15);
+    }
+}
@@ -114,8 +114,48 @@ const OPENAI_CHAT_SCHEMA: &str = r#"{
             "additionalProperties": {"type": "number"}
         },
         "user": {"type": "string"},
-        "tools": {"type": "array"},
-        "tool_choice": {},
+        "tools": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["type", "function"],
+                "properties": {
+                    "type": {"type": "string", "enum": ["function"]},
+                    "function": {
+                        "type": "object",
+                        "required": ["name", "parameters"],
+                        "properties": {
+                            "name": {
+                                "type": "string",
+                                "pattern": "^[a-zA-Z0-9_-]{1,64}$"
+                            },
+                            "description": {"type": "string"},
+                            "parameters": {"type": "object"},
+                            "strict": {"type": "boolean"}
+                        }
+                    }
+                }
+            }
+        },
+        "tool_choice": {
+            "oneOf": [
+                {"type": "string", "enum": ["none", "auto", "required"]},
+                {
+                    "type": "object",
+                    "required": ["type", "function"],
+                    "properties": {
+                        "type": {"type": "string", "enum": ["function"]},
+                        "function": {
+                            "type": "object",
+                            "required": ["name"],
+                            "properties": {
+                                "name": {"type": "string", "minLength": 1}
+                            }
+                        }
+                    }
+                }
+            ]
+        },
         "response_format": {"type": "object"},
         "seed": {"type": "integer"}
     },
@@ -443,6 +483,55 @@ mod tests {
         assert!(result.valid, "Errors: {:?}", result.errors);
     }
 
+    #[test]
+    fn test_valid_openai_chat_with_tools() {
+        let body = r#"{
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "What's the weather?"}],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Get the current weather",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                }
+            }],
+            "tool_choice": {"type": "function", "function": {"name": "get_weather"}}
+        }"#;
+        let result = validate_openai_chat(body);
+        assert!(result.valid, "Errors: {:?}", result.errors);
+    }
+
+    #[test]
+    fn test_openai_chat_tool_missing_name() {
+        let body = r#"{
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "What's the weather?"}],
+            "tools": [{
+                "type": "function",
+                "function": {"parameters": {"type": "object"}}
+            }]
+        }"#;
+        let result = validate_openai_chat(body);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.contains("name")));
+    }
+
+    #[test]
+    fn test_openai_chat_invalid_tool_choice() {
+        let body = r#"{
+            "model": "gpt-4",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "tool_choice": "sometimes"
+        }"#;
+        let result = validate_openai_chat(body);
+        assert!(!result.valid);
+    }
+
     #[test]
     fn test_openai_chat_missing_model() {
         let body = r#"{
@@ -3,10 +3,17 @@
 //! Provides sliding window rate limiting by client IP, with support for:
 //! - Requests per minute
 //! - Tokens per minute (estimated)
+//! - A global window shared by every client, checked alongside the
+//!   per-client one
+//! - Trusted client networks that bypass every limit
+//! - A burst allowance that lets a client briefly exceed its request limit
 
+use crate::GatewayError;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
 
 /// Rate limiter configuration
@@ -18,6 +25,12 @@ pub struct RateLimitConfig {
     pub tokens_per_minute: u32,
     /// Window duration for rate limiting
     pub window_duration: Duration,
+    /// Extra requests a client may make beyond `requests_per_minute` (0 = none)
+    ///
+    /// The allowance is a bucket spent by requests over the limit and
+    /// refilled at each window reset by the headroom the client left unused,
+    /// so a short burst passes but sustained overage is still throttled.
+    pub burst: u32,
 }
 
 impl Default for RateLimitConfig {
@@ -26,6 +39,7 @@ impl Default for RateLimitConfig {
             requests_per_minute: 0,
             tokens_per_minute: 0,
             window_duration: Duration::from_secs(60),
+            burst: 0,
         }
     }
 }
@@ -52,6 +66,10 @@ pub struct RateLimitResult {
     pub token_limit: u32,
     /// Seconds until window resets
     pub reset_seconds: u64,
+    /// Unix timestamp (seconds) at which the window resets (0 when disabled)
+    pub reset_epoch: u64,
+    /// Burst requests still available beyond the request limit
+    pub burst_remaining: u32,
     /// Which limit was exceeded (if any)
     pub exceeded_limit: Option<ExceededLimit>,
 }
@@ -60,9 +78,35 @@ pub struct RateLimitResult {
 pub enum ExceededLimit {
     Requests,
     Tokens,
+    /// Requests across all clients
+    GlobalRequests,
+    /// Tokens across all clients
+    GlobalTokens,
+}
+
+impl ExceededLimit {
+    /// Whether the shared global window was exceeded
+    pub fn is_global(&self) -> bool {
+        matches!(
+            self,
+            ExceededLimit::GlobalRequests | ExceededLimit::GlobalTokens
+        )
+    }
+
+    /// Whether a token limit was exceeded
+    pub fn is_tokens(&self) -> bool {
+        matches!(self, ExceededLimit::Tokens | ExceededLimit::GlobalTokens)
+    }
 }
 
 impl RateLimitResult {
+    /// Seconds a denied client should wait before retrying
+    ///
+    /// Never zero, so clients always back off at least briefly.
+    pub fn retry_after_seconds(&self) -> u64 {
+        self.reset_seconds.max(1)
+    }
+
     pub fn allowed(
         request_count: u32,
         request_limit: u32,
@@ -77,6 +121,8 @@ impl RateLimitResult {
             token_count,
             token_limit,
             reset_seconds,
+            reset_epoch: 0,
+            burst_remaining: 0,
             exceeded_limit: None,
         }
     }
@@ -96,6 +142,8 @@ impl RateLimitResult {
             token_count,
             token_limit,
             reset_seconds,
+            reset_epoch: 0,
+            burst_remaining: 0,
             exceeded_limit: Some(exceeded),
         }
     }
@@ -106,18 +154,25 @@ impl RateLimitResult {
 struct WindowEntry {
     /// When this window started
     window_start: Instant,
+    /// Wall-clock time this window started, for absolute reset timestamps
+    window_start_wall: SystemTime,
     /// Request count in current window
     request_count: u32,
     /// Token count in current window
     token_count: u32,
+    /// Unspent burst allowance, capped by the configured burst when read
+    burst_credits: u32,
 }
 
 impl WindowEntry {
     fn new() -> Self {
         Self {
             window_start: Instant::now(),
+            window_start_wall: SystemTime::now(),
             request_count: 0,
             token_count: 0,
+            // A new client starts with the full allowance
+            burst_credits: u32::MAX,
         }
     }
 
@@ -126,21 +181,153 @@ impl WindowEntry {
         self.window_start.elapsed() >= window_duration
     }
 
-    /// Reset the window
-    fn reset(&mut self) {
+    /// Burst requests available under `config`
+    fn burst_available(&self, config: &RateLimitConfig) -> u32 {
+        self.burst_credits.min(config.burst)
+    }
+
+    /// Reset the window, refilling the burst allowance by unused headroom
+    fn reset(&mut self, config: &RateLimitConfig) {
+        let unused = config
+            .requests_per_minute
+            .saturating_sub(self.request_count);
+        self.burst_credits = self
+            .burst_available(config)
+            .saturating_add(unused)
+            .min(config.burst);
         self.window_start = Instant::now();
+        self.window_start_wall = SystemTime::now();
         self.request_count = 0;
         self.token_count = 0;
     }
 
-    /// Get seconds until window resets
+    /// Get seconds until window resets, rounded up to whole seconds
     fn seconds_until_reset(&self, window_duration: Duration) -> u64 {
         let elapsed = self.window_start.elapsed();
         if elapsed >= window_duration {
             0
         } else {
-            (window_duration - elapsed).as_secs()
+            (window_duration - elapsed).as_secs_f64().ceil() as u64
+        }
+    }
+
+    /// Unix timestamp at which the window resets, rounded up to whole seconds
+    fn reset_epoch(&self, window_duration: Duration) -> u64 {
+        (self.window_start_wall + window_duration)
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64().ceil() as u64)
+            .unwrap_or(0)
+    }
+
+    /// Which limit recording a request of `tokens` would exceed, if any
+    fn exceeded(&self, config: &RateLimitConfig, tokens: u32) -> Option<ExceededLimit> {
+        if config.requests_per_minute > 0
+            && self.request_count >= config.requests_per_minute
+            && self.burst_available(config) == 0
+        {
+            return Some(ExceededLimit::Requests);
+        }
+        if config.tokens_per_minute > 0
+            && self.token_count.saturating_add(tokens) > config.tokens_per_minute
+        {
+            return Some(ExceededLimit::Tokens);
+        }
+        None
+    }
+
+    /// Count an allowed request, spending burst allowance once over the limit
+    fn record(&mut self, config: &RateLimitConfig, tokens: u32) {
+        if config.requests_per_minute > 0 && self.request_count >= config.requests_per_minute {
+            self.burst_credits = self.burst_available(config).saturating_sub(1);
         }
+        self.request_count += 1;
+        self.token_count = self.token_count.saturating_add(tokens);
+    }
+
+    /// Current counts against the configured limits
+    fn result(&self, config: &RateLimitConfig, exceeded: Option<ExceededLimit>) -> RateLimitResult {
+        RateLimitResult {
+            allowed: exceeded.is_none(),
+            request_count: self.request_count,
+            request_limit: config.requests_per_minute,
+            token_count: self.token_count,
+            token_limit: config.tokens_per_minute,
+            reset_seconds: self.seconds_until_reset(config.window_duration),
+            reset_epoch: self.reset_epoch(config.window_duration),
+            burst_remaining: self.burst_available(config),
+            exceeded_limit: exceeded,
+        }
+    }
+}
+
+/// An IP network in CIDR notation; a bare address is a single-host network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    /// Whether the address falls inside this network
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = GatewayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || GatewayError::InvalidNetwork(s.to_string());
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// A single window shared by every client
+///
+/// One instance is shared by the default and all tenant limiters, so it
+/// protects an upstream quota no matter who is calling.
+pub struct GlobalRateLimit {
+    config: RateLimitConfig,
+    entry: Mutex<WindowEntry>,
+}
+
+impl GlobalRateLimit {
+    /// Create a global window with the given limits
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            entry: Mutex::new(WindowEntry::new()),
+        }
+    }
+
+    /// Check if the global limit is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
     }
 }
 
@@ -149,6 +336,10 @@ pub struct RateLimiter {
     config: RateLimitConfig,
     /// Per-client rate limit state, keyed by client identifier (usually IP)
     state: Arc<Mutex<HashMap<String, WindowEntry>>>,
+    /// Window shared with every other limiter (None = no global limit)
+    global: Option<Arc<GlobalRateLimit>>,
+    /// Client networks exempt from every limit
+    bypass: Vec<IpNetwork>,
 }
 
 impl RateLimiter {
@@ -157,73 +348,129 @@ impl RateLimiter {
         Self {
             config,
             state: Arc::new(Mutex::new(HashMap::new())),
+            global: None,
+            bypass: Vec::new(),
+        }
+    }
+
+    /// Create a rate limiter that also enforces a shared global window
+    pub fn with_global(config: RateLimitConfig, global: Arc<GlobalRateLimit>) -> Self {
+        Self {
+            global: global.is_enabled().then_some(global),
+            ..Self::new(config)
         }
     }
 
+    /// Exempt clients in the given networks from per-client and global limits
+    pub fn with_bypass(mut self, networks: Vec<IpNetwork>) -> Self {
+        self.bypass = networks;
+        self
+    }
+
+    /// Whether a client is exempt from rate limiting
+    ///
+    /// Only client identifiers that parse as an IP address can match.
+    pub fn is_bypassed(&self, client_id: &str) -> bool {
+        if self.bypass.is_empty() {
+            return false;
+        }
+        client_id
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| self.bypass.iter().any(|net| net.contains(&ip)))
+    }
+
+    /// Check if any per-client or global limit is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled() || self.global.is_some()
+    }
+
     /// Check if a request is allowed and record it
     ///
     /// Returns the rate limit result with current counts and limits.
-    /// If allowed, the request and tokens are counted.
+    /// The per-client and global windows are both checked before either is
+    /// updated, so a request denied by one is not counted by the other.
+    /// If allowed, the request and tokens are counted once in each.
     pub async fn check_and_record(
         &self,
         client_id: &str,
         estimated_tokens: u32,
     ) -> RateLimitResult {
-        if !self.config.is_enabled() {
+        self.check_windows(client_id, estimated_tokens, true).await
+    }
+
+    /// Check if a request would be allowed, without recording it
+    ///
+    /// For requests already rejected for another reason, which must not
+    /// spend the client's or the global window.
+    pub async fn check(&self, client_id: &str, estimated_tokens: u32) -> RateLimitResult {
+        self.check_windows(client_id, estimated_tokens, false).await
+    }
+
+    /// Check the per-client and global windows, recording an allowed
+    /// request when `record` is set
+    async fn check_windows(
+        &self,
+        client_id: &str,
+        estimated_tokens: u32,
+        record: bool,
+    ) -> RateLimitResult {
+        if !self.is_enabled() {
             return RateLimitResult::allowed(0, 0, 0, 0, 0);
         }
 
+        // Lock order is always client state, then the global window
         let mut state = self.state.lock().await;
-        let entry = state
-            .entry(client_id.to_string())
-            .or_insert_with(WindowEntry::new);
-
-        // Reset window if expired
-        if entry.is_expired(self.config.window_duration) {
-            entry.reset();
-        }
+        let mut client = if self.config.is_enabled() {
+            let entry = state
+                .entry(client_id.to_string())
+                .or_insert_with(WindowEntry::new);
+            // Reset window if expired
+            if entry.is_expired(self.config.window_duration) {
+                entry.reset(&self.config);
+            }
+            if let Some(exceeded) = entry.exceeded(&self.config, estimated_tokens) {
+                return entry.result(&self.config, Some(exceeded));
+            }
+            Some(entry)
+        } else {
+            None
+        };
 
-        let reset_seconds = entry.seconds_until_reset(self.config.window_duration);
+        let mut global = match self.global {
+            Some(ref global) => {
+                let mut entry = global.entry.lock().await;
+                if entry.is_expired(global.config.window_duration) {
+                    entry.reset(&global.config);
+                }
+                if let Some(exceeded) = entry.exceeded(&global.config, estimated_tokens) {
+                    let exceeded = match exceeded {
+                        ExceededLimit::Tokens => ExceededLimit::GlobalTokens,
+                        _ => ExceededLimit::GlobalRequests,
+                    };
+                    return entry.result(&global.config, Some(exceeded));
+                }
+                Some((entry, &global.config))
+            }
+            None => None,
+        };
 
-        // Check request limit
-        if self.config.requests_per_minute > 0
-            && entry.request_count >= self.config.requests_per_minute
-        {
-            return RateLimitResult::denied(
-                entry.request_count,
-                self.config.requests_per_minute,
-                entry.token_count,
-                self.config.tokens_per_minute,
-                reset_seconds,
-                ExceededLimit::Requests,
-            );
+        // Record the request in every window
+        if record {
+            if let Some((ref mut entry, config)) = global {
+                entry.record(config, estimated_tokens);
+            }
+            if let Some(ref mut entry) = client {
+                entry.record(&self.config, estimated_tokens);
+            }
         }
-
-        // Check token limit
-        if self.config.tokens_per_minute > 0
-            && entry.token_count + estimated_tokens > self.config.tokens_per_minute
-        {
-            return RateLimitResult::denied(
-                entry.request_count,
-                self.config.requests_per_minute,
-                entry.token_count,
-                self.config.tokens_per_minute,
-                reset_seconds,
-                ExceededLimit::Tokens,
-            );
+        match client {
+            Some(entry) => entry.result(&self.config, None),
+            // Only the global limit applies: report its counts
+            None => match global {
+                Some((entry, config)) => entry.result(config, None),
+                None => RateLimitResult::allowed(0, 0, 0, 0, 0),
+            },
         }
-
-        // Record the request
-        entry.request_count += 1;
-        entry.token_count += estimated_tokens;
-
-        RateLimitResult::allowed(
-            entry.request_count,
-            self.config.requests_per_minute,
-            entry.token_count,
-            self.config.tokens_per_minute,
-            reset_seconds,
-        )
     }
 
     /// Clean up expired entries to prevent memory growth
@@ -246,6 +493,20 @@ impl RateLimiter {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_check_does_not_record() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: 1,
+            ..Default::default()
+        });
+        assert!(limiter.check("client1", 10).await.allowed);
+        assert_eq!(limiter.get_state("client1").await, Some((0, 0)));
+
+        assert!(limiter.check_and_record("client1", 10).await.allowed);
+        assert!(!limiter.check("client1", 10).await.allowed);
+        assert_eq!(limiter.get_state("client1").await, Some((1, 10)));
+    }
+
     #[tokio::test]
     async fn test_rate_limiter_disabled() {
         let limiter = RateLimiter::new(RateLimitConfig::default());
@@ -259,6 +520,7 @@ mod tests {
             requests_per_minute: 3,
             tokens_per_minute: 0,
             window_duration: Duration::from_secs(60),
+            burst: 0,
         };
         let limiter = RateLimiter::new(config);
 
@@ -275,12 +537,115 @@ mod tests {
         assert_eq!(result.exceeded_limit, Some(ExceededLimit::Requests));
     }
 
+    #[test]
+    fn test_ip_network_contains() {
+        let net: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains(&"10.1.200.3".parse().unwrap()));
+        assert!(!net.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(!net.contains(&"::ffff:10.1.0.1".parse().unwrap()));
+
+        let host: IpNetwork = "192.168.1.5".parse().unwrap();
+        assert!(host.contains(&"192.168.1.5".parse().unwrap()));
+        assert!(!host.contains(&"192.168.1.6".parse().unwrap()));
+
+        let v6: IpNetwork = "fd00::/8".parse().unwrap();
+        assert!(v6.contains(&"fd12:3456::1".parse().unwrap()));
+        let any: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(&"8.8.8.8".parse().unwrap()));
+
+        for invalid in ["10.0.0.0/33", "10.0.0/8", "host.internal", "::/129"] {
+            assert_eq!(
+                invalid.parse::<IpNetwork>(),
+                Err(GatewayError::InvalidNetwork(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_bypassed() {
+        let limiter = RateLimiter::new(RateLimitConfig::default())
+            .with_bypass(vec!["10.0.0.0/8".parse().unwrap()]);
+        assert!(limiter.is_bypassed("10.9.8.7"));
+        assert!(!limiter.is_bypassed("11.0.0.1"));
+        assert!(!limiter.is_bypassed("api-key-client"));
+    }
+
+    fn global(requests_per_minute: u32, tokens_per_minute: u32) -> Arc<GlobalRateLimit> {
+        Arc::new(GlobalRateLimit::new(RateLimitConfig {
+            requests_per_minute,
+            tokens_per_minute,
+            window_duration: Duration::from_secs(60),
+            burst: 0,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_shared_across_clients() {
+        let config = RateLimitConfig {
+            requests_per_minute: 3,
+            tokens_per_minute: 0,
+            window_duration: Duration::from_secs(60),
+            burst: 0,
+        };
+        let limiter = RateLimiter::with_global(config, global(4, 0));
+
+        for client in ["client1", "client2", "client1", "client2"] {
+            assert!(limiter.check_and_record(client, 0).await.allowed);
+        }
+
+        // Both clients are under their own cap of 3, but the shared window is full
+        let result = limiter.check_and_record("client1", 0).await;
+        assert!(!result.allowed);
+        assert_eq!(result.exceeded_limit, Some(ExceededLimit::GlobalRequests));
+        assert_eq!(result.request_limit, 4);
+
+        // The globally denied request is not counted against the client
+        assert_eq!(limiter.get_state("client1").await, Some((2, 0)));
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_shared_across_limiters() {
+        let shared = global(0, 1000);
+        let tenant_a = RateLimiter::with_global(RateLimitConfig::default(), Arc::clone(&shared));
+        let tenant_b = RateLimiter::with_global(RateLimitConfig::default(), shared);
+
+        let result = tenant_a.check_and_record("client1", 600).await;
+        assert!(result.allowed);
+        // Only the global limit applies, so its counts are reported
+        assert_eq!((result.token_count, result.token_limit), (600, 1000));
+
+        let result = tenant_b.check_and_record("client2", 600).await;
+        assert_eq!(result.exceeded_limit, Some(ExceededLimit::GlobalTokens));
+        assert!(tenant_b.check_and_record("client2", 400).await.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_client_denial_not_counted_globally() {
+        let config = RateLimitConfig {
+            requests_per_minute: 1,
+            tokens_per_minute: 0,
+            window_duration: Duration::from_secs(60),
+            burst: 0,
+        };
+        let limiter = RateLimiter::with_global(config, global(2, 0));
+
+        assert!(limiter.check_and_record("client1", 0).await.allowed);
+        let result = limiter.check_and_record("client1", 0).await;
+        assert_eq!(result.exceeded_limit, Some(ExceededLimit::Requests));
+
+        // client1's denied request left room for one more globally
+        assert!(limiter.check_and_record("client2", 0).await.allowed);
+        let result = limiter.check_and_record("client3", 0).await;
+        assert_eq!(result.exceeded_limit, Some(ExceededLimit::GlobalRequests));
+    }
+
     #[tokio::test]
     async fn test_token_limit() {
         let config = RateLimitConfig {
             requests_per_minute: 0,
             tokens_per_minute: 1000,
             window_duration: Duration::from_secs(60),
+            burst: 0,
         };
         let limiter = RateLimiter::new(config);
 
@@ -298,6 +663,42 @@ mod tests {
         let result = limiter.check_and_record("client1", 200).await;
         assert!(!result.allowed);
         assert_eq!(result.exceeded_limit, Some(ExceededLimit::Tokens));
+        assert!(result.retry_after_seconds() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_reset_seconds_rounds_up() {
+        let config = RateLimitConfig {
+            requests_per_minute: 1,
+            tokens_per_minute: 0,
+            window_duration: Duration::from_millis(500),
+            burst: 0,
+        };
+        let limiter = RateLimiter::new(config);
+
+        limiter.check_and_record("client1", 0).await;
+        let result = limiter.check_and_record("client1", 0).await;
+        assert!(!result.allowed);
+        assert_eq!(result.reset_seconds, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reset_epoch_matches_reset_seconds() {
+        let config = RateLimitConfig {
+            requests_per_minute: 5,
+            tokens_per_minute: 0,
+            window_duration: Duration::from_secs(60),
+            burst: 0,
+        };
+        let limiter = RateLimiter::new(config);
+
+        let result = limiter.check_and_record("client1", 0).await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let expected = now + result.reset_seconds;
+        assert!(result.reset_epoch.abs_diff(expected) <= 1);
     }
 
     #[tokio::test]
@@ -306,6 +707,7 @@ mod tests {
             requests_per_minute: 2,
             tokens_per_minute: 0,
             window_duration: Duration::from_secs(60),
+            burst: 0,
         };
         let limiter = RateLimiter::new(config);
 
@@ -328,6 +730,7 @@ mod tests {
             requests_per_minute: 2,
             tokens_per_minute: 0,
             window_duration: Duration::from_millis(100), // Very short window for testing
+            burst: 0,
         };
         let limiter = RateLimiter::new(config);
 
@@ -348,12 +751,65 @@ mod tests {
         assert_eq!(result.request_count, 1);
     }
 
+    #[tokio::test]
+    async fn test_burst_allows_short_overage() {
+        let config = RateLimitConfig {
+            requests_per_minute: 3,
+            tokens_per_minute: 0,
+            window_duration: Duration::from_secs(60),
+            burst: 2,
+        };
+        let limiter = RateLimiter::new(config);
+
+        for i in 1..=5 {
+            let result = limiter.check_and_record("client1", 0).await;
+            assert!(result.allowed, "Request {} should be allowed", i);
+            assert_eq!(result.burst_remaining, 2u32.min(5 - i));
+        }
+        let result = limiter.check_and_record("client1", 0).await;
+        assert!(!result.allowed);
+        assert_eq!(result.exceeded_limit, Some(ExceededLimit::Requests));
+        assert_eq!(result.request_count, 5);
+    }
+
+    #[tokio::test]
+    async fn test_sustained_overage_throttled() {
+        let config = RateLimitConfig {
+            requests_per_minute: 3,
+            tokens_per_minute: 0,
+            window_duration: Duration::from_millis(50),
+            burst: 2,
+        };
+        let limiter = RateLimiter::new(config);
+
+        async fn allowed_in_window(limiter: &RateLimiter, attempts: u32) -> u32 {
+            let mut allowed = 0;
+            for _ in 0..attempts {
+                if limiter.check_and_record("client1", 0).await.allowed {
+                    allowed += 1;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            allowed
+        }
+
+        // The burst is spent in the first window and not earned back
+        assert_eq!(allowed_in_window(&limiter, 10).await, 5);
+        assert_eq!(allowed_in_window(&limiter, 10).await, 3);
+        assert_eq!(allowed_in_window(&limiter, 10).await, 3);
+
+        // A quiet window leaves headroom that refills the allowance
+        assert_eq!(allowed_in_window(&limiter, 1).await, 1);
+        assert_eq!(allowed_in_window(&limiter, 10).await, 5);
+    }
+
     #[tokio::test]
     async fn test_combined_limits() {
         let config = RateLimitConfig {
             requests_per_minute: 10,
             tokens_per_minute: 500,
             window_duration: Duration::from_secs(60),
+            burst: 0,
         };
         let limiter = RateLimiter::new(config);
 
@@ -375,6 +831,7 @@ mod tests {
             requests_per_minute: 10,
             tokens_per_minute: 0,
             window_duration: Duration::from_millis(50),
+            burst: 0,
         };
         let limiter = RateLimiter::new(config);
 
@@ -0,0 +1,140 @@
+//! Header and secret redaction for logs and audit metadata.
+//!
+//! Requests carry provider credentials (`authorization`, `x-api-key`,
+//! `api-key`, `x-goog-api-key`) and session cookies. Anything that writes
+//! headers out goes through [`redact_headers`] so raw tokens never reach a
+//! log line. Credentials in the agent's own config are held as [`Secret`].
+
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Replacement for a sensitive header value
+pub const REDACTED: &str = "***";
+
+/// Replacement for a secret config value in dumps and diffs
+pub const REDACTED_CONFIG_VALUE: &str = "[redacted]";
+
+/// A credential held in configuration
+///
+/// `Debug` prints `***`; use [`Secret::expose`] where the value is sent.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// The secret value itself
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Serialize an optional secret as `[redacted]`, for `serialize_with`
+pub fn serialize_secret<S: Serializer>(secret: &Option<Secret>, s: S) -> Result<S::Ok, S::Error> {
+    secret.as_ref().map(|_| REDACTED_CONFIG_VALUE).serialize(s)
+}
+
+/// Headers that always carry credentials
+pub const DEFAULT_SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+    "cookie",
+    "set-cookie",
+];
+
+/// Whether a header carries credentials, by the built-in list or `extra`
+pub fn is_sensitive(name: &str, extra: &[String]) -> bool {
+    DEFAULT_SENSITIVE_HEADERS
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(name))
+        || extra.iter().any(|h| h.eq_ignore_ascii_case(name))
+}
+
+/// Copy headers for logging, with sensitive values replaced by `***`
+///
+/// Names are kept as sent; the map is sorted so log lines are stable.
+pub fn redact_headers(
+    headers: &HashMap<String, Vec<String>>,
+    extra: &[String],
+) -> BTreeMap<String, Vec<String>> {
+    headers
+        .iter()
+        .map(|(name, values)| {
+            let values = if is_sensitive(name, extra) {
+                vec![REDACTED.to_string(); values.len()]
+            } else {
+                values.clone()
+            };
+            (name.clone(), values)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_headers_redacted() {
+        let redacted = redact_headers(
+            &headers(&[
+                ("Authorization", "Bearer sk-live-123"),
+                ("x-api-key", "sk-ant-456"),
+                ("Api-Key", "azure-789"),
+                ("Proxy-Authorization", "Basic dXNlcjpwYXNz"),
+                ("x-goog-api-key", "AIzaSy-123"),
+                ("Cookie", "session=abc"),
+                ("set-cookie", "session=def"),
+                ("content-type", "application/json"),
+            ]),
+            &[],
+        );
+        assert_eq!(redacted["Authorization"], vec!["***"]);
+        assert_eq!(redacted["x-api-key"], vec!["***"]);
+        assert_eq!(redacted["Api-Key"], vec!["***"]);
+        assert_eq!(redacted["Proxy-Authorization"], vec!["***"]);
+        assert_eq!(redacted["x-goog-api-key"], vec!["***"]);
+        assert_eq!(redacted["Cookie"], vec!["***"]);
+        assert_eq!(redacted["set-cookie"], vec!["***"]);
+        assert_eq!(redacted["content-type"], vec!["application/json"]);
+    }
+
+    #[test]
+    fn test_secret_is_not_printed() {
+        let secret = Some(Secret::from("sk-mod-123".to_string()));
+        assert_eq!(format!("{:?}", secret), "Some(***)");
+        let mut json = Vec::new();
+        serialize_secret(&secret, &mut serde_json::Serializer::new(&mut json)).unwrap();
+        assert_eq!(json, br#""[redacted]""#);
+        assert_eq!(secret.unwrap().expose(), "sk-mod-123");
+    }
+
+    #[test]
+    fn test_extra_sensitive_headers() {
+        let extra = vec!["X-Internal-Token".to_string()];
+        let redacted = redact_headers(&headers(&[("x-internal-token", "secret")]), &extra);
+        assert_eq!(redacted["x-internal-token"], vec!["***"]);
+        assert!(!is_sensitive("x-tenant-id", &extra));
+    }
+}
@@ -0,0 +1,180 @@
+//! Per-request state storage.
+//!
+//! Body chunks for one request arrive one at a time, interleaved with chunks
+//! for every other in-flight request. A single map behind one lock serializes
+//! all of that work; [`ShardedStore`] spreads requests over several locks
+//! keyed by a hash of the correlation ID, so only requests that land in the
+//! same shard wait on each other.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::{Mutex, MutexGuard};
+
+/// Default number of shards
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// Storage for the state of in-flight requests, keyed by correlation ID
+///
+/// Calls for different keys may run concurrently; calls for the same key
+/// arrive in order, since the proxy sends one request's events in sequence.
+pub trait RequestStore<V>: Send + Sync {
+    /// Store state for a new request
+    ///
+    /// Returns the value back if the key is already in flight, leaving the
+    /// existing state untouched.
+    fn insert_new(&self, key: String, value: V) -> Result<(), V>;
+
+    /// Run `f` on a request's state; `false` if the key is unknown
+    fn update(&self, key: &str, f: &mut dyn FnMut(&mut V)) -> bool;
+
+    /// Remove and return a request's state
+    fn remove(&self, key: &str) -> Option<V>;
+
+    /// Whether state is held for the key
+    fn contains_key(&self, key: &str) -> bool;
+
+    /// Number of requests held
+    fn len(&self) -> usize;
+
+    /// Whether no requests are held
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every request's state
+    fn clear(&self);
+}
+
+/// [`RequestStore`] split over several independently locked maps
+#[derive(Debug)]
+pub struct ShardedStore<V> {
+    hasher: RandomState,
+    shards: Box<[Mutex<HashMap<String, V>>]>,
+}
+
+impl<V> ShardedStore<V> {
+    /// Create a store with `shards` locks (at least one)
+    pub fn new(shards: usize) -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Lock the shard holding `key`
+    ///
+    /// A panic while a shard was held leaves plain data behind, so a
+    /// poisoned lock is used as is.
+    fn shard(&self, key: &str) -> MutexGuard<'_, HashMap<String, V>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lock every shard in turn
+    fn all_shards(&self) -> impl Iterator<Item = MutexGuard<'_, HashMap<String, V>>> {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl<V> Default for ShardedStore<V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHARDS)
+    }
+}
+
+impl<V: Send> RequestStore<V> for ShardedStore<V> {
+    fn insert_new(&self, key: String, value: V) -> Result<(), V> {
+        let mut shard = self.shard(&key);
+        if shard.contains_key(&key) {
+            return Err(value);
+        }
+        shard.insert(key, value);
+        Ok(())
+    }
+
+    fn update(&self, key: &str, f: &mut dyn FnMut(&mut V)) -> bool {
+        match self.shard(key).get_mut(key) {
+            Some(value) => {
+                f(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&self, key: &str) -> Option<V> {
+        self.shard(key).remove(key)
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.shard(key).contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.all_shards().map(|shard| shard.len()).sum()
+    }
+
+    fn clear(&self) {
+        for mut shard in self.all_shards() {
+            shard.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_insert_update_remove() {
+        let store: ShardedStore<Vec<u8>> = ShardedStore::new(4);
+        assert!(store.insert_new("a".to_string(), vec![1]).is_ok());
+        assert_eq!(store.insert_new("a".to_string(), vec![9]), Err(vec![9]));
+        assert!(store.update("a", &mut |v| v.push(2)));
+        assert!(!store.update("b", &mut |v| v.push(3)));
+        assert!(store.contains_key("a"));
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.remove("a"), Some(vec![1, 2]));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_zero_shards_still_stores() {
+        let store: ShardedStore<u32> = ShardedStore::new(0);
+        store.insert_new("a".to_string(), 1).unwrap();
+        store.insert_new("b".to_string(), 2).unwrap();
+        assert_eq!(store.len(), 2);
+        store.clear();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_parallel_keys_stay_separate() {
+        let store: Arc<ShardedStore<Vec<usize>>> = Arc::new(ShardedStore::default());
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let store = Arc::clone(&store);
+                std::thread::spawn(move || {
+                    for i in 0..200 {
+                        let key = format!("{}-{}", t, i);
+                        store.insert_new(key.clone(), Vec::new()).unwrap();
+                        for _ in 0..3 {
+                            assert!(store.update(&key, &mut |v| v.push(t)));
+                        }
+                        assert_eq!(store.remove(&key), Some(vec![t; 3]));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+//! Recent detection statistics.
+//!
+//! Keeps a bounded ring of recent detections so operators can see which
+//! reason codes, providers, and models tripped most often over a rolling
+//! window, and serves the summary as JSON on `GET /stats`.
+
+use crate::providers::AiProvider;
+use crate::AnalysisResult;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+/// Default number of detections kept in the ring
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Default rolling window for the summary
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Largest request head read from a stats client
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// Longest a stats client may take to send its request and read the answer
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause after a failed accept, so running out of file descriptors does not
+/// become a busy loop
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// One reason code recorded for one request
+#[derive(Debug, Clone)]
+struct DetectionEvent {
+    at: Instant,
+    reason_code: String,
+    provider: AiProvider,
+    model: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct StatsState {
+    /// Recent detections, oldest first
+    recent: VecDeque<DetectionEvent>,
+    /// Lifetime count per reason code
+    totals: HashMap<String, u64>,
+}
+
+/// Bounded store of recent detections
+///
+/// Recording takes one short lock; summaries are computed on demand.
+#[derive(Debug)]
+pub struct DetectionStats {
+    capacity: usize,
+    window: Duration,
+    state: Mutex<StatsState>,
+}
+
+/// A ranked count in a [`StatsSummary`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatsEntry {
+    pub key: String,
+    pub count: u64,
+}
+
+/// Detection counts over the rolling window, highest first
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsSummary {
+    pub window_seconds: u64,
+    /// Detections in the window (one per reason code per request)
+    pub total: u64,
+    pub reason_codes: Vec<StatsEntry>,
+    pub providers: Vec<StatsEntry>,
+    pub models: Vec<StatsEntry>,
+}
+
+impl Default for DetectionStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_WINDOW)
+    }
+}
+
+impl DetectionStats {
+    /// Create a store keeping at most `capacity` detections from the last `window`
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            window,
+            state: Mutex::new(StatsState::default()),
+        }
+    }
+
+    /// Record every reason code of an analysis result
+    pub fn record(&self, result: &AnalysisResult) {
+        if result.reason_codes.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for code in &result.reason_codes {
+            *state.totals.entry(code.clone()).or_insert(0) += 1;
+            if state.recent.len() == self.capacity {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(DetectionEvent {
+                at: now,
+                reason_code: code.clone(),
+                provider: result.provider,
+                model: result.model.clone(),
+            });
+        }
+    }
+
+    /// Lifetime detection count per reason code
+    pub fn totals(&self) -> HashMap<String, u64> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.totals.clone()
+    }
+
+    /// Summarize detections within the rolling window
+    pub fn summary(&self) -> StatsSummary {
+        let recent: Vec<DetectionEvent> = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            // Drop expired events so the ring only holds the window
+            while state
+                .recent
+                .front()
+                .is_some_and(|e| e.at.elapsed() > self.window)
+            {
+                state.recent.pop_front();
+            }
+            state.recent.iter().cloned().collect()
+        };
+
+        let mut reason_codes = HashMap::new();
+        let mut providers = HashMap::new();
+        let mut models = HashMap::new();
+        for event in &recent {
+            *reason_codes.entry(event.reason_code.clone()).or_insert(0) += 1;
+            *providers
+                .entry(event.provider.as_str().to_string())
+                .or_insert(0) += 1;
+            if let Some(ref model) = event.model {
+                *models.entry(model.clone()).or_insert(0) += 1;
+            }
+        }
+
+        StatsSummary {
+            window_seconds: self.window.as_secs(),
+            total: recent.len() as u64,
+            reason_codes: ranked(reason_codes),
+            providers: ranked(providers),
+            models: ranked(models),
+        }
+    }
+}
+
+/// Sort counts descending, ties by key
+fn ranked(counts: HashMap<String, u64>) -> Vec<StatsEntry> {
+    let mut entries: Vec<StatsEntry> = counts
+        .into_iter()
+        .map(|(key, count)| StatsEntry { key, count })
+        .collect();
+    entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    entries
+}
+
+/// Serve `GET /stats` on the listener until the task is dropped
+///
+/// A failed accept is logged and retried, and a client that does not finish
+/// within [`CONNECTION_TIMEOUT`] is disconnected.
+pub async fn serve(listener: TcpListener, stats: Arc<DetectionStats>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(error = %e, "Failed to accept stats connection");
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let stats = Arc::clone(&stats);
+        tokio::spawn(async move {
+            match tokio::time::timeout(CONNECTION_TIMEOUT, handle_connection(stream, &stats)).await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => debug!(peer = %peer, error = %e, "Stats connection failed"),
+                Err(_) => debug!(peer = %peer, "Stats connection timed out"),
+            }
+        });
+    }
+}
+
+/// Answer a single HTTP/1.x request and close the connection
+async fn handle_connection(mut stream: TcpStream, stats: &DetectionStats) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let request_line = String::from_utf8_lossy(&head);
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) if path.split('?').next() == Some("/stats") => (
+            "200 OK",
+            serde_json::to_string(&stats.summary()).unwrap_or_default(),
+        ),
+        (Some(_), Some(_)) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => ("400 Bad Request", r#"{"error":"bad request"}"#.to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(codes: &[&str], provider: AiProvider, model: &str) -> AnalysisResult {
+        let mut result = AnalysisResult::new(provider);
+        result.model = Some(model.to_string());
+        result.reason_codes = codes.iter().map(|c| c.to_string()).collect();
+        result
+    }
+
+    #[test]
+    fn test_summary_ranks_by_count() {
+        let stats = DetectionStats::default();
+        stats.record(&detection(&["PII_DETECTED"], AiProvider::OpenAI, "gpt-4"));
+        stats.record(&detection(
+            &["PROMPT_INJECTION", "PII_DETECTED"],
+            AiProvider::Anthropic,
+            "claude-3",
+        ));
+        stats.record(&detection(&["PII_DETECTED"], AiProvider::OpenAI, "gpt-4"));
+        stats.record(&AnalysisResult::new(AiProvider::OpenAI));
+
+        let summary = stats.summary();
+        assert_eq!(summary.total, 4);
+        assert_eq!(
+            summary.reason_codes,
+            vec![
+                StatsEntry {
+                    key: "PII_DETECTED".to_string(),
+                    count: 3
+                },
+                StatsEntry {
+                    key: "PROMPT_INJECTION".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        // Two detections each: ties are ordered by key
+        let providers: Vec<_> = summary.providers.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(providers, vec!["anthropic", "openai"]);
+        assert_eq!(summary.models[0].key, "claude-3");
+        assert_eq!(stats.totals().get("PII_DETECTED"), Some(&3));
+    }
+
+    #[test]
+    fn test_ring_is_bounded() {
+        let stats = DetectionStats::new(2, DEFAULT_WINDOW);
+        for _ in 0..5 {
+            stats.record(&detection(&["PII_DETECTED"], AiProvider::OpenAI, "gpt-4"));
+        }
+        assert_eq!(stats.summary().total, 2);
+        // Lifetime totals are not bounded by the ring
+        assert_eq!(stats.totals().get("PII_DETECTED"), Some(&5));
+    }
+
+    #[test]
+    fn test_window_expires_old_detections() {
+        let stats = DetectionStats::new(10, Duration::ZERO);
+        stats.record(&detection(&["PII_DETECTED"], AiProvider::OpenAI, "gpt-4"));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(stats.summary().total, 0);
+    }
+}